@@ -0,0 +1,284 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::{FnArg, Ident, ReturnType, Token, TraitItem, TraitItemMethod};
+
+use crate::helpers::{extract_signature, is_self, ExtractedSignature};
+
+
+
+struct DispatchOneArgs {
+    /// Call the next picked child when the result is a failure.
+    pub failover: bool,
+    /// Call a second child when the first one doesn't answer in time.
+    pub hedge: bool,
+    pub item: TraitItem,
+}
+
+impl Parse for DispatchOneArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        // [failover | hedge;] item
+        let (mut failover, mut hedge) = (false, false);
+        if input.peek(Ident) {
+            let option: Ident = input.parse()?;
+            if option == "failover" {
+                failover = true;
+            } else if option == "hedge" {
+                hedge = true;
+            } else {
+                return Err(syn::Error::new(option.span(), "unknown option of dispatch_one"));
+            }
+            input.parse::<Token![;]>()?;
+        }
+        Ok(Self {
+            failover,
+            hedge,
+            item: input.parse()?,
+        })
+    }
+}
+
+pub fn dispatch_one_impl(args: TokenStream) -> TokenStream {
+    // Try to parse the item as a header, report other elements as errors
+    let DispatchOneArgs { failover, hedge, item } = match syn::parse2(args) {
+        Ok(v) => v,
+        Err(e) => return e.to_compile_error(),
+    };
+    let TraitItemMethod {
+        attrs,
+        sig,
+        default: _,
+        semi_token: _,
+    } = match item {
+        TraitItem::Method(v) => v,
+        _ => return quote! {},
+    };
+
+    // Process the modifiers and extract the signature
+    let ExtractedSignature {
+        modifiers,
+        ident,
+        generics,
+        inputs,
+        output,
+    } = extract_signature(attrs, sig);
+
+    // Process the arguments, extract to names
+    let args = match inputs.first() {
+        Some(car) if is_self(car) => inputs
+            .iter()
+            .skip(1)
+            .map(|x| match x {
+                FnArg::Receiver(_) => unreachable!(),
+                FnArg::Typed(val) => val.pat.to_owned(),
+            })
+            .collect::<Vec<_>>(),
+        _ => {
+            return quote! {
+                compile_error!("Cable methods must be object method to pick a client");
+            }
+        }
+    };
+
+    // Pick the child mutably for `&mut self` methods, and through `&self` otherwise
+    let (pick, binding) = match inputs.first() {
+        Some(FnArg::Receiver(receiver)) if receiver.mutability.is_none() => (quote! { pick_shared }, quote! { it }),
+        _ => (quote! { pick_child }, quote! { mut it }),
+    };
+
+    // Process the return type and function body
+    let (typespec, body) = match output {
+        ReturnType::Default if hedge => (
+            quote! {},
+            quote! {
+                self.hedge(move |it, _| it.#ident(#(#args.clone()),*));
+            },
+        ),
+        ReturnType::Type(_, ty) if hedge => (
+            quote! { -> #ty where #ty: Default + Send + 'static },
+            quote! {
+                match self.hedge(move |it, _| it.#ident(#(#args.clone()),*)) {
+                    Some(res) => res,
+                    None => Default::default(),
+                }
+            },
+        ),
+        ReturnType::Default if failover => (
+            quote! {},
+            quote! {
+                if let Some((id, it)) = self.pick_child() {
+                    it.#ident(#(#args),*);
+                    self.report_call(id, true);
+                }
+            },
+        ),
+        ReturnType::Type(_, ty) if failover => (
+            quote! { -> #ty where #ty: frincoe::cable::Outcome },
+            quote! {
+                let mut last = None;
+                let mut tried = Vec::new();
+                while let Some((id, it)) = self.pick_untried(&tried) {
+                    tried.push(id);
+                    let res: #ty = it.#ident(#(#args.clone()),*);
+                    let failed = frincoe::cable::Outcome::is_failure(&res);
+                    self.report_call(id, !failed);
+                    if !failed {
+                        return res;
+                    }
+                    last = Some(res);
+                }
+                last.unwrap_or_else(frincoe::cable::Outcome::unavailable)
+            },
+        ),
+        ReturnType::Default => (
+            quote! {},
+            quote! {
+                if let Some(#binding) = self.#pick() {
+                    it.#ident(#(#args),*);
+                }
+            },
+        ),
+        ReturnType::Type(_, ty) => (
+            quote! { -> #ty where #ty: Default },
+            quote! {
+                match self.#pick() {
+                    Some(#binding) => it.#ident(#(#args),*),
+                    None => Default::default(),
+                }
+            },
+        ),
+    };
+
+    quote! { #modifiers fn #ident #generics (#inputs) #typespec { #body } }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use quote::quote;
+
+    use crate::dispatch_one_impl;
+
+
+
+    #[test]
+    fn different_decls() {
+        assert_eq!(
+            dispatch_one_impl(quote! { fn f(&mut self, x: i32, y: i32) -> Bundle<i32>; }).to_string(),
+            quote! {
+                fn f(&mut self, x: i32, y: i32) -> Bundle<i32> where Bundle<i32>: Default {
+                    match self.pick_child() {
+                        Some(mut it) => it.f(x, y),
+                        None => Default::default(),
+                    }
+                }
+            }
+            .to_string(),
+        );
+        // Void result
+        assert_eq!(
+            dispatch_one_impl(quote! { fn f(&mut self); }).to_string(),
+            quote! {
+                fn f(&mut self) {
+                    if let Some(mut it) = self.pick_child() {
+                        it.f();
+                    }
+                }
+            }
+            .to_string(),
+        );
+        // Shared calls
+        assert_eq!(
+            dispatch_one_impl(quote! { fn f(&self, x: i32) -> i32; }).to_string(),
+            quote! {
+                fn f(&self, x: i32) -> i32 where i32: Default {
+                    match self.pick_shared() {
+                        Some(it) => it.f(x),
+                        None => Default::default(),
+                    }
+                }
+            }
+            .to_string(),
+        );
+        // Other items are ignored
+        assert!(dispatch_one_impl(quote! { type T; }).is_empty());
+    }
+
+    #[test]
+    fn failover() {
+        assert_eq!(
+            dispatch_one_impl(quote! { failover; fn f(&mut self, x: String) -> T; }).to_string(),
+            quote! {
+                fn f(&mut self, x: String) -> T where T: frincoe::cable::Outcome {
+                    let mut last = None;
+                    let mut tried = Vec::new();
+                    while let Some((id, it)) = self.pick_untried(&tried) {
+                        tried.push(id);
+                        let res: T = it.f(x.clone());
+                        let failed = frincoe::cable::Outcome::is_failure(&res);
+                        self.report_call(id, !failed);
+                        if !failed {
+                            return res;
+                        }
+                        last = Some(res);
+                    }
+                    last.unwrap_or_else(frincoe::cable::Outcome::unavailable)
+                }
+            }
+            .to_string(),
+        );
+        // Nothing to fail without results
+        assert_eq!(
+            dispatch_one_impl(quote! { failover; fn f(&mut self); }).to_string(),
+            quote! {
+                fn f(&mut self) {
+                    if let Some((id, it)) = self.pick_child() {
+                        it.f();
+                        self.report_call(id, true);
+                    }
+                }
+            }
+            .to_string(),
+        );
+    }
+
+    #[test]
+    fn hedge() {
+        assert_eq!(
+            dispatch_one_impl(quote! { hedge; fn f(&self, x: String, y: u32) -> T; }).to_string(),
+            quote! {
+                fn f(&self, x: String, y: u32) -> T where T: Default + Send + 'static {
+                    match self.hedge(move |it, _| it.f(x.clone(), y.clone())) {
+                        Some(res) => res,
+                        None => Default::default(),
+                    }
+                }
+            }
+            .to_string(),
+        );
+        assert_eq!(
+            dispatch_one_impl(quote! { hedge; fn f(&self); }).to_string(),
+            quote! {
+                fn f(&self) {
+                    self.hedge(move |it, _| it.f());
+                }
+            }
+            .to_string(),
+        );
+    }
+
+    #[test]
+    fn errornous() {
+        assert_eq!(
+            dispatch_one_impl(quote! { fn f(s: i32) -> i32; }).to_string(),
+            quote! {
+                compile_error!("Cable methods must be object method to pick a client");
+            }
+            .to_string(),
+        );
+        assert!(dispatch_one_impl(quote! { unknown; fn f(&self); })
+            .to_string()
+            .contains("unknown option of dispatch_one"));
+    }
+}
@@ -1,24 +1,123 @@
 use proc_macro2::TokenStream;
 use quote::quote;
-use syn::{FnArg, ReturnType, TraitItem, TraitItemMethod};
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{parenthesized, FnArg, Ident, Member, Pat, ReturnType, Token, TraitItem, TraitItemMethod};
 
 use crate::helpers::{extract_signature, is_self, ExtractedSignature};
 
 
 
+struct DispatchSubArgs {
+    /// Call these members of `self` instead of iterating over the children.
+    pub fields: Option<Vec<Member>>,
+    /// Record the children producing the results.
+    pub attributed: bool,
+    /// Iterate over the children through `DynCable`.
+    pub dynamic: bool,
+    /// Call only the child picked by this argument.
+    pub shard: Option<Ident>,
+    /// Notify the observers of `self` around each call.
+    pub observed: bool,
+    /// Hand the call to `self` when there's no child to call.
+    pub dead_letter: bool,
+    /// Post the calls to the mailboxes of the children.
+    pub mailbox: bool,
+    pub item: TraitItem,
+}
+
+impl Parse for DispatchSubArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        // [option, ...;] item
+        let mut fields = None;
+        let mut attributed = false;
+        let mut dynamic = false;
+        let mut shard = None;
+        let mut observed = false;
+        let mut dead_letter = false;
+        let mut mailbox = false;
+        if input.peek(Ident) {
+            loop {
+                let option: Ident = input.parse()?;
+                if option == "fields" {
+                    let content;
+                    parenthesized!(content in input);
+                    let members = Punctuated::<Member, Token![,]>::parse_terminated(&content)?;
+                    fields = Some(members.into_iter().collect());
+                } else if option == "attributed" {
+                    attributed = true;
+                } else if option == "dynamic" {
+                    dynamic = true;
+                } else if option == "shard" {
+                    input.parse::<Token![=]>()?;
+                    shard = Some(input.parse()?);
+                } else if option == "observed" {
+                    observed = true;
+                } else if option == "dead_letter" {
+                    dead_letter = true;
+                } else if option == "mailbox" {
+                    mailbox = true;
+                } else {
+                    return Err(syn::Error::new(option.span(), "unknown option of dispatch_sub"));
+                }
+                if input.peek(Token![;]) {
+                    input.parse::<Token![;]>()?;
+                    break;
+                }
+                input.parse::<Token![,]>()?;
+            }
+        }
+        Ok(Self {
+            fields,
+            attributed,
+            dynamic,
+            shard,
+            observed,
+            dead_letter,
+            mailbox,
+            item: input.parse()?,
+        })
+    }
+}
+
 pub fn dispatch_sub_impl(args: TokenStream) -> TokenStream {
     // Try to parse the item as a header, report other elements as errors
+    let DispatchSubArgs {
+        fields,
+        attributed,
+        dynamic,
+        shard,
+        observed,
+        dead_letter,
+        mailbox,
+        item,
+    } = match syn::parse2(args) {
+        Ok(v) => v,
+        Err(e) => return e.to_compile_error(),
+    };
+    if mailbox && (fields.is_some() || attributed || dynamic || shard.is_some() || observed || dead_letter) {
+        return quote! {
+            compile_error!("`mailbox` can't be used with other options");
+        };
+    }
+    if dead_letter && fields.is_some() {
+        return quote! {
+            compile_error!("`dead_letter` can't be used with `fields`, which always have recipients");
+        };
+    }
+    if let Some(key) = &shard {
+        if fields.is_some() || dynamic {
+            return syn::Error::new(key.span(), "`shard` can't be used with `fields` or `dynamic`").to_compile_error();
+        }
+    }
     let TraitItemMethod {
         attrs,
         sig,
         default: _,
         semi_token: _,
-    } = match syn::parse2::<TraitItem>(args) {
-        Ok(v) => match v {
-            TraitItem::Method(v) => v,
-            _ => return quote! {},
-        },
-        Err(e) => return e.to_compile_error(),
+    } = match item {
+        TraitItem::Method(v) => v,
+        _ => return quote! {},
     };
 
     // Process the modifiers and extract the signature
@@ -32,33 +131,175 @@ pub fn dispatch_sub_impl(args: TokenStream) -> TokenStream {
 
     // Process the arguments, extract to names
     let args = match inputs.first() {
-        Some(car) if is_self(car) => inputs.iter().skip(1).map(|x| match x {
-            FnArg::Receiver(_) => unreachable!(),
-            FnArg::Typed(val) => val.pat.to_owned(),
-        }),
+        Some(car) if is_self(car) => inputs
+            .iter()
+            .skip(1)
+            .map(|x| match x {
+                FnArg::Receiver(_) => unreachable!(),
+                FnArg::Typed(val) => val.pat.to_owned(),
+            })
+            .collect::<Vec<_>>(),
         _ => {
             return quote! {
                 compile_error!("Cable methods must be object method to iterate over the clients");
             }
         }
     };
+    if let Some(key) = &shard {
+        if !args
+            .iter()
+            .any(|x| matches!(&**x, Pat::Ident(arg) if arg.ident == *key))
+        {
+            return syn::Error::new(key.span(), "the argument to shard by is not found").to_compile_error();
+        }
+    }
+
+    // Calls through the mailboxes are closures run on the workers, so the arguments are cloned for each child
+    if mailbox {
+        let (typespec, body) = match output {
+            ReturnType::Default => (
+                quote! {},
+                quote! {
+                    if let Err(e) = self.post(move |it| {
+                        it.#ident(#(#args.clone()),*);
+                    }) {
+                        self.report_rejected(e);
+                    }
+                },
+            ),
+            ReturnType::Type(_, ty) => (
+                quote! { -> #ty where #ty: Extend<#ty> + Default + Send + 'static },
+                quote! {
+                    let mut res: #ty = Default::default();
+                    for it in self.ask(move |it| it.#ident(#(#args.clone()),*)) {
+                        res.extend(it);
+                    }
+                    res
+                },
+            ),
+        };
+        return quote! { #modifiers fn #ident #generics (#inputs) #typespec { #body } };
+    }
 
     // Process the return type and function body
     let typespec = match output {
         ReturnType::Default => quote! {},
+        ReturnType::Type(_, ref ty) if dead_letter => {
+            quote! { -> #ty where #ty: Extend<#ty> + Default + frincoe::cable::Undeliverable }
+        }
         ReturnType::Type(_, ref ty) => quote! { -> #ty where #ty: Extend<#ty> + Default },
     };
-    let body = match output {
+    let attributed = attributed && matches!(output, ReturnType::Type(..));
+    let name = ident.to_string();
+    let call = |it: TokenStream, id: TokenStream| {
+        let call = match output {
+            ReturnType::Default => quote! { #it.#ident(#(#args),*); },
+            ReturnType::Type(..) if attributed => quote! { res.extend_from(#id, #it.#ident(#(#args),*)); },
+            ReturnType::Type(..) => quote! { res.extend(#it.#ident(#(#args),*)); },
+        };
+        let call = if observed {
+            quote! {
+                {
+                    let _call = observers.call(#name, #id);
+                    #call
+                }
+            }
+        } else {
+            call
+        };
+        if dead_letter {
+            quote! {
+                delivered = true;
+                #call
+            }
+        } else {
+            call
+        }
+    };
+    let (iter_child, iter_child_with_id) = if dynamic {
+        (quote! { iter_child_dyn }, quote! { iter_child_with_id_dyn })
+    } else {
+        (quote! { iter_child }, quote! { iter_child_with_id })
+    };
+    let calls = match fields {
+        None if shard.is_some() => {
+            let call = call(quote! { it }, quote! { id });
+            let entry = if attributed || observed {
+                quote! { (id, it) }
+            } else {
+                quote! { (_, it) }
+            };
+            quote! {
+                for #entry in self.shard_child(&#shard) {
+                    #call
+                }
+            }
+        }
+        None => {
+            let call = call(quote! { it }, quote! { id });
+            if attributed || observed {
+                quote! {
+                    for (id, it) in self.#iter_child_with_id() {
+                        #call
+                    }
+                }
+            } else {
+                quote! {
+                    for it in self.#iter_child() {
+                        #call
+                    }
+                }
+            }
+        }
+        Some(fields) => {
+            let calls = fields.iter().enumerate().map(|(i, field)| {
+                let i = i as u32;
+                call(
+                    quote! { self.#field },
+                    quote! { frincoe::cable::ConnectionId::positional(#i) },
+                )
+            });
+            quote! { #(#calls)* }
+        }
+    };
+    let calls = match output {
+        _ if !dead_letter => calls,
         ReturnType::Default => quote! {
-            for it in self.iter_child() {
-                it.#ident(#(#args),*);
+            let mut delivered = false;
+            #calls
+            if !delivered {
+                match self.dead_letter(#name) {
+                    frincoe::cable::DeadLetter::Forward(it) => it.#ident(#(#args),*),
+                    frincoe::cable::DeadLetter::Error(e) => self.report_undelivered(e),
+                    frincoe::cable::DeadLetter::Drop => {}
+                }
             }
         },
+        ReturnType::Type(..) => quote! {
+            let mut delivered = false;
+            #calls
+            if !delivered {
+                match self.dead_letter(#name) {
+                    frincoe::cable::DeadLetter::Forward(it) => res.extend(it.#ident(#(#args),*)),
+                    frincoe::cable::DeadLetter::Error(e) => res = frincoe::cable::Undeliverable::undelivered(e),
+                    frincoe::cable::DeadLetter::Drop => {}
+                }
+            }
+        },
+    };
+    let calls = if observed {
+        quote! {
+            let observers = self.observers();
+            #calls
+        }
+    } else {
+        calls
+    };
+    let body = match output {
+        ReturnType::Default => calls,
         ReturnType::Type(_, ty) => quote! {
             let mut res: #ty = Default::default();
-            for it in self.iter_child() {
-                res.extend(it.#ident(#(#args),*));
-            }
+            #calls
             res
         },
     };
@@ -139,6 +380,263 @@ mod tests {
         );
     }
 
+    #[test]
+    fn fields() {
+        assert_eq!(
+            dispatch_sub_impl(quote! { fields(0, 1, named); fn f(&mut self, x: i32) -> T; }).to_string(),
+            quote! {
+                fn f(&mut self, x: i32) -> T where T: Extend<T> + Default {
+                    let mut res: T = Default::default();
+                    res.extend(self.0.f(x));
+                    res.extend(self.1.f(x));
+                    res.extend(self.named.f(x));
+                    res
+                }
+            }
+            .to_string(),
+        );
+        assert_eq!(
+            dispatch_sub_impl(quote! { fields(a); fn f(&self); }).to_string(),
+            quote! {
+                fn f(&self) {
+                    self.a.f();
+                }
+            }
+            .to_string(),
+        );
+    }
+
+    #[test]
+    fn attributed() {
+        assert_eq!(
+            dispatch_sub_impl(quote! { attributed; fn f(&mut self, x: i32) -> T; }).to_string(),
+            quote! {
+                fn f(&mut self, x: i32) -> T where T: Extend<T> + Default {
+                    let mut res: T = Default::default();
+                    for (id, it) in self.iter_child_with_id() {
+                        res.extend_from(id, it.f(x));
+                    }
+                    res
+                }
+            }
+            .to_string(),
+        );
+        assert_eq!(
+            dispatch_sub_impl(quote! { fields(a, b), attributed; fn f(&mut self) -> T; }).to_string(),
+            quote! {
+                fn f(&mut self) -> T where T: Extend<T> + Default {
+                    let mut res: T = Default::default();
+                    res.extend_from(frincoe::cable::ConnectionId::positional(0u32), self.a.f());
+                    res.extend_from(frincoe::cable::ConnectionId::positional(1u32), self.b.f());
+                    res
+                }
+            }
+            .to_string(),
+        );
+        // Nothing to attribute without results
+        assert_eq!(
+            dispatch_sub_impl(quote! { attributed; fn f(&mut self); }).to_string(),
+            dispatch_sub_impl(quote! { fn f(&mut self); }).to_string(),
+        );
+    }
+
+    #[test]
+    fn dynamic() {
+        assert_eq!(
+            dispatch_sub_impl(quote! { dynamic; fn f(&mut self, x: i32) -> T; }).to_string(),
+            quote! {
+                fn f(&mut self, x: i32) -> T where T: Extend<T> + Default {
+                    let mut res: T = Default::default();
+                    for it in self.iter_child_dyn() {
+                        res.extend(it.f(x));
+                    }
+                    res
+                }
+            }
+            .to_string(),
+        );
+        assert_eq!(
+            dispatch_sub_impl(quote! { dynamic, attributed; fn f(&mut self); }).to_string(),
+            quote! {
+                fn f(&mut self) {
+                    for it in self.iter_child_dyn() {
+                        it.f();
+                    }
+                }
+            }
+            .to_string(),
+        );
+    }
+
+    #[test]
+    fn shard() {
+        assert_eq!(
+            dispatch_sub_impl(quote! { shard = key; fn f(&mut self, key: u64, x: i32) -> T; }).to_string(),
+            quote! {
+                fn f(&mut self, key: u64, x: i32) -> T where T: Extend<T> + Default {
+                    let mut res: T = Default::default();
+                    for (_, it) in self.shard_child(&key) {
+                        res.extend(it.f(key, x));
+                    }
+                    res
+                }
+            }
+            .to_string(),
+        );
+        assert_eq!(
+            dispatch_sub_impl(quote! { shard = key, attributed; fn f(&mut self, key: &str) -> T; }).to_string(),
+            quote! {
+                fn f(&mut self, key: &str) -> T where T: Extend<T> + Default {
+                    let mut res: T = Default::default();
+                    for (id, it) in self.shard_child(&key) {
+                        res.extend_from(id, it.f(key));
+                    }
+                    res
+                }
+            }
+            .to_string(),
+        );
+        assert!(dispatch_sub_impl(quote! { shard = missing; fn f(&self, key: u64); })
+            .to_string()
+            .contains("the argument to shard by is not found"));
+        assert!(
+            dispatch_sub_impl(quote! { shard = key, fields(a); fn f(&self, key: u64); })
+                .to_string()
+                .contains("can't be used with")
+        );
+    }
+
+    #[test]
+    fn observed() {
+        assert_eq!(
+            dispatch_sub_impl(quote! { observed; fn f(&mut self, x: i32) -> T; }).to_string(),
+            quote! {
+                fn f(&mut self, x: i32) -> T where T: Extend<T> + Default {
+                    let mut res: T = Default::default();
+                    let observers = self.observers();
+                    for (id, it) in self.iter_child_with_id() {
+                        {
+                            let _call = observers.call("f", id);
+                            res.extend(it.f(x));
+                        }
+                    }
+                    res
+                }
+            }
+            .to_string(),
+        );
+        assert_eq!(
+            dispatch_sub_impl(quote! { fields(a), observed; fn g(&self); }).to_string(),
+            quote! {
+                fn g(&self) {
+                    let observers = self.observers();
+                    {
+                        let _call = observers.call("g", frincoe::cable::ConnectionId::positional(0u32));
+                        self.a.g();
+                    }
+                }
+            }
+            .to_string(),
+        );
+        assert_eq!(
+            dispatch_sub_impl(quote! { shard = key, observed; fn f(&mut self, key: u64); }).to_string(),
+            quote! {
+                fn f(&mut self, key: u64) {
+                    let observers = self.observers();
+                    for (id, it) in self.shard_child(&key) {
+                        {
+                            let _call = observers.call("f", id);
+                            it.f(key);
+                        }
+                    }
+                }
+            }
+            .to_string(),
+        );
+    }
+
+    #[test]
+    fn dead_letter() {
+        assert_eq!(
+            dispatch_sub_impl(quote! { dead_letter; fn f(&mut self, x: i32) -> T; }).to_string(),
+            quote! {
+                fn f(&mut self, x: i32) -> T where T: Extend<T> + Default + frincoe::cable::Undeliverable {
+                    let mut res: T = Default::default();
+                    let mut delivered = false;
+                    for it in self.iter_child() {
+                        delivered = true;
+                        res.extend(it.f(x));
+                    }
+                    if !delivered {
+                        match self.dead_letter("f") {
+                            frincoe::cable::DeadLetter::Forward(it) => res.extend(it.f(x)),
+                            frincoe::cable::DeadLetter::Error(e) => res = frincoe::cable::Undeliverable::undelivered(e),
+                            frincoe::cable::DeadLetter::Drop => {}
+                        }
+                    }
+                    res
+                }
+            }
+            .to_string(),
+        );
+        assert_eq!(
+            dispatch_sub_impl(quote! { shard = key, dead_letter; fn f(&mut self, key: u64); }).to_string(),
+            quote! {
+                fn f(&mut self, key: u64) {
+                    let mut delivered = false;
+                    for (_, it) in self.shard_child(&key) {
+                        delivered = true;
+                        it.f(key);
+                    }
+                    if !delivered {
+                        match self.dead_letter("f") {
+                            frincoe::cable::DeadLetter::Forward(it) => it.f(key),
+                            frincoe::cable::DeadLetter::Error(e) => self.report_undelivered(e),
+                            frincoe::cable::DeadLetter::Drop => {}
+                        }
+                    }
+                }
+            }
+            .to_string(),
+        );
+        assert!(dispatch_sub_impl(quote! { fields(a), dead_letter; fn f(&self); })
+            .to_string()
+            .contains("can't be used with"));
+    }
+
+    #[test]
+    fn mailbox() {
+        assert_eq!(
+            dispatch_sub_impl(quote! { mailbox; fn f(&mut self, x: String, y: u32); }).to_string(),
+            quote! {
+                fn f(&mut self, x: String, y: u32) {
+                    if let Err(e) = self.post(move |it| {
+                        it.f(x.clone(), y.clone());
+                    }) {
+                        self.report_rejected(e);
+                    }
+                }
+            }
+            .to_string(),
+        );
+        assert_eq!(
+            dispatch_sub_impl(quote! { mailbox; fn f(&self) -> T; }).to_string(),
+            quote! {
+                fn f(&self) -> T where T: Extend<T> + Default + Send + 'static {
+                    let mut res: T = Default::default();
+                    for it in self.ask(move |it| it.f()) {
+                        res.extend(it);
+                    }
+                    res
+                }
+            }
+            .to_string(),
+        );
+        assert!(dispatch_sub_impl(quote! { mailbox, attributed; fn f(&self); })
+            .to_string()
+            .contains("can't be used with other options"));
+    }
+
     #[test]
     fn errornous() {
         assert_eq!(
@@ -155,5 +653,8 @@ mod tests {
             }
             .to_string(),
         );
+        assert!(dispatch_sub_impl(quote! { unknown; fn f(&self); })
+            .to_string()
+            .contains("unknown option of dispatch_sub"));
     }
 }
@@ -67,7 +67,7 @@ The path of the source trait will be relative to the source file where the macro
 
 Grammar:
 ```text
-make_dispatcher!(impl[<Generics>]
+make_dispatcher!([#[attributes]] impl[<Generics>]
     [{ trait definition {} } |"path/to/definition/file"::Trait::Path]
     [as Actual::Trait::Path] for TargetProvider [as RequestType -> ResponseType]
     [where Other: Predicate + Clause]);
@@ -83,10 +83,12 @@ e.g. `TargetProviderTraitRequest` and `TargetProviderTraitResponse`.
 
 Each method `fn some_method(arg1: Type1, arg2: Type2) -> ReturnType` in the trait
 will be generated an entry in `RequestType` as `SomeMethod(Type1, Type2)`,
-and an entry in `ResponseType` as `SomeMethod(ReturnType)`
+and an entry in `ResponseType` as `SomeMethod(ReturnType)`, or `SomeMethod(())` without a return type
 (transform the name into class case, and split the types into request and response types respectively);
 and a match hand in `dispatch` function calling the implement in provider will be generated.
 
+The attributes are applied to `RequestType`, e.g. `#[derive(Clone)]` to keep the requests for replaying.
+
 A method with `self` argument won't add `self` into the request type,
 and would be invoked with `self` of the dispatcher.
 */
@@ -107,6 +109,35 @@ Apart from that `Self` should impl [`Cable`],
 the return type `T`s of the methods should be `Extend<T> + Default`
 to allow the macro to pack them as the final result.
 
+Options can be given as extra arguments, separated by commas:
+```text
+inject_implement!(... in dispatch_sub[(option, ...)])
+```
+- `fields(member, ...)`: call the given members of `self` in order instead of iterating over the children,
+  so that clients of different types are called statically, e.g. for [`TupleCable`]
+  with `fields(0, 1, 2)`; `Self` doesn't need to impl [`Cable`] in this case.
+- `attributed`: record which child produced each item of the result with `Bundle::extend_from`,
+  so the return types should be [`Bundle`]s;
+  the ids are from `Cable::iter_child_with_id`, or the positions with `fields`.
+- `dynamic`: iterate over the children through [`DynCable`] instead of [`Cable`],
+  so that `Self` can be a trait object like `Box<dyn DynCable<C>>`.
+- `shard = arg`: call only the child picked by the argument named `arg` with `shard_child(&arg)`,
+  e.g. on a [`ShardedCable`]; it can't be used with `fields` or `dynamic`.
+- `observed`: notify the [`Observers`] returned by `self.observers()` before and after each call to a child,
+  with the name of the method and the id of the child, e.g. on an [`Observed`] cable;
+  the end of the call is notified by a guard, even if the child panics.
+- `dead_letter`: when there's no child to call, ask `self.dead_letter(method)` what to do, e.g. on a [`DeadLetterCable`],
+  which forwards the call to a sink client, returns an error, or drops it as if it were delivered.
+  The return types of all the methods with results should also be [`Undeliverable`] to hold the error,
+  whatever the policy is, as it's only known at runtime;
+  methods without results hand the error to `self.report_undelivered(error)` instead.
+  It can't be used with `fields`.
+- `mailbox`: enqueue the calls to the children with `self.post` and `self.ask` like [`MailboxCable`] does,
+  so methods without results return at once, handing the errors of the full mailboxes to `self.report_rejected(errors)`,
+  and the ones with results wait for the children;
+  the arguments should be `Clone + Send + Sync + 'static`, and the return types should also be `Send + 'static`.
+  It can't be used with other options.
+
 Other declarations besides methods in the trait are ignored,
 if it's needed, use a specialization (i.e. `default const V: T = ...;` etc.) to provide them a value.
 
@@ -114,6 +145,15 @@ See document of [frincoe-rpc](../frincoe_rpc/index.html) for the difference of p
 and document of [`Cable`] for on which the adapter is used.
 
 [`Cable`]: ../frincoe/cable/trait.Cable.html
+[`TupleCable`]: ../frincoe/cable/struct.TupleCable.html
+[`DynCable`]: ../frincoe/cable/trait.DynCable.html
+[`ShardedCable`]: ../frincoe/cable/struct.ShardedCable.html
+[`Observers`]: ../frincoe/cable/struct.Observers.html
+[`Observed`]: ../frincoe/cable/struct.Observed.html
+[`DeadLetterCable`]: ../frincoe/cable/struct.DeadLetterCable.html
+[`Undeliverable`]: ../frincoe/cable/trait.Undeliverable.html
+[`MailboxCable`]: ../frincoe/cable/struct.MailboxCable.html
+[`Bundle`]: ../frincoe/cable/struct.Bundle.html
  */
 #[cfg(feature = "adapters")]
 #[doc(cfg(feature = "adapters"))]
@@ -124,6 +164,51 @@ pub fn dispatch_sub(args: TokenStream) -> TokenStream {
 
 
 
+mod dispatch_one;
+use dispatch_one::dispatch_one_impl;
+
+/**
+Adapter for [`inject_implement!`] to make load-balancing cables, which call exactly one child.
+
+`Self` should provide `pick_child` and `pick_shared` like [`BalancedCable`] does:
+the call is forwarded to the child picked by `pick_child` for methods taking `&mut self`,
+or by `pick_shared` for methods taking `&self`, and its result is returned as is;
+the picked child is dropped after the call, even if the call panics, which completes the call.
+If there's no child to pick, `Default::default()` is returned,
+so the return types `T` of the methods should be `Default`.
+
+With the `failover` option, i.e. `dispatch_one(failover)`, `Self` should provide `pick_child`, `pick_untried`
+and `report_call` like [`FailoverCable`] does: when the result is a failure according to [`Outcome`],
+the call is retried on the next child picked by `pick_untried`, skipping the ones tried by the call,
+until one succeeds or no child is left,
+and `report_call` is notified whether each call succeeds;
+so the return types should be [`Outcome`]s instead of `Default`s, and the arguments should be `Clone`.
+When all the children fail, the result of the last one is returned,
+and when no child can be picked, e.g. all of them are backing off, [`Outcome::unavailable`] is returned.
+
+With the `hedge` option, i.e. `dispatch_one(hedge)`, `Self` should provide `hedge` like [`HedgedCable`] does:
+the call is forwarded to a child on another thread, and to a second one if the first doesn't answer in time,
+returning the first answer; so the arguments should be `Clone + Send + Sync + 'static`,
+and the return types should be `Default + Send + 'static`, the default being returned when there's no child.
+When both of the calls panic, the panic is resumed on the caller.
+
+Other declarations besides methods in the trait are ignored, as in [`dispatch_sub`].
+
+[`BalancedCable`]: ../frincoe/cable/struct.BalancedCable.html
+[`FailoverCable`]: ../frincoe/cable/struct.FailoverCable.html
+[`Outcome`]: ../frincoe/cable/trait.Outcome.html
+[`Outcome::unavailable`]: ../frincoe/cable/trait.Outcome.html#tymethod.unavailable
+[`HedgedCable`]: ../frincoe/cable/struct.HedgedCable.html
+ */
+#[cfg(feature = "adapters")]
+#[doc(cfg(feature = "adapters"))]
+#[proc_macro]
+pub fn dispatch_one(args: TokenStream) -> TokenStream {
+    dispatch_one_impl(args.into()).into()
+}
+
+
+
 mod forward_sub;
 use forward_sub::forward_sub_impl;
 
@@ -2,13 +2,15 @@ use inflector::Inflector;
 use proc_macro2::TokenStream;
 use quote::quote;
 use syn::parse::Parse;
-use syn::{FnArg, Generics, Ident, ReturnType, Token, TraitItem, Type, TypePath, WhereClause};
+use syn::{Attribute, FnArg, Generics, Ident, ReturnType, Token, TraitItem, Type, TypePath, WhereClause};
 
 use crate::helpers::{is_self, TraitSpec};
 
 
 
 struct DispatcherArgs {
+    /// Attributes of the request type.
+    pub attrs: Vec<Attribute>,
     pub generics: Option<Generics>,
     pub spec: TraitSpec,
     pub target: Type,
@@ -19,7 +21,8 @@ struct DispatcherArgs {
 
 impl Parse for DispatcherArgs {
     fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
-        // impl[<Generics>] ...
+        // [#[attributes]] impl[<Generics>] ...
+        let attrs = input.call(Attribute::parse_outer)?;
         input.parse::<Token![impl]>()?;
         let generics = if input.peek(Token![<]) {
             Some(input.parse()?)
@@ -48,6 +51,7 @@ impl Parse for DispatcherArgs {
             None
         };
         Ok(Self {
+            attrs,
             generics,
             spec,
             target,
@@ -60,6 +64,7 @@ impl Parse for DispatcherArgs {
 
 pub fn make_dispatcher_impl(args: TokenStream) -> TokenStream {
     let DispatcherArgs {
+        attrs,
         generics,
         spec,
         target,
@@ -115,7 +120,7 @@ pub fn make_dispatcher_impl(args: TokenStream) -> TokenStream {
             TraitItem::Method(func) => Some((
                 &func.sig.inputs,
                 match func.sig.output {
-                    ReturnType::Default => quote! {},
+                    ReturnType::Default => quote! { () },
                     ReturnType::Type(_, ref ty) => quote! { #ty },
                 },
             )),
@@ -146,6 +151,7 @@ pub fn make_dispatcher_impl(args: TokenStream) -> TokenStream {
         .unzip();
 
     quote! {
+        #(#attrs)*
         enum #generics #request {
             #(#variants(#req_types),)*
         }
@@ -157,7 +163,7 @@ pub fn make_dispatcher_impl(args: TokenStream) -> TokenStream {
             type Response = #response;
             fn dispatch(&mut self, request: Self::Request) -> Self::Response {
                 match request {
-                    #(#request :: #variants(#req_args) => #selfs #methods(#req_args),)*
+                    #(#request :: #variants(#req_args) => #response :: #variants(#selfs #methods(#req_args)),)*
                 }
             }
         }
@@ -176,6 +182,7 @@ mod tests {
     fn several() {
         assert_eq!(
             make_dispatcher_impl(quote! {
+                #[derive(Clone)]
                 impl {
                     trait T {
                         const T: i32;
@@ -187,6 +194,7 @@ mod tests {
             })
             .to_string(),
             quote! {
+                #[derive(Clone)]
                 enum StructTRequest {
                     F1(i32, i64),
                     F2(i32),
@@ -194,7 +202,7 @@ mod tests {
                 }
                 enum StructTResponse {
                     F1(Vec<i32>),
-                    F2(),
+                    F2(()),
                     F3Snake(Box<i32>),
                 }
                 impl frincoe_rpc::Dispatcher for pathed::Struct
@@ -203,9 +211,9 @@ mod tests {
                     type Response = StructTResponse;
                     fn dispatch(&mut self, request: Self::Request) -> Self::Response {
                         match request {
-                            StructTRequest::F1(a, b) => self.f1(a, b),
-                            StructTRequest::F2(u) => self.f2(u),
-                            StructTRequest::F3Snake() => Self::f3_snake(),
+                            StructTRequest::F1(a, b) => StructTResponse::F1(self.f1(a, b)),
+                            StructTRequest::F2(u) => StructTResponse::F2(self.f2(u)),
+                            StructTRequest::F3Snake() => StructTResponse::F3Snake(Self::f3_snake()),
                         }
                     }
                 }
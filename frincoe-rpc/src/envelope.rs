@@ -0,0 +1,232 @@
+use std::cell::RefCell;
+use std::collections::btree_map::{self, BTreeMap};
+use std::sync::Arc;
+
+use crate::Peer;
+
+
+
+/**
+Headers of a call, like sender identity, timestamps, trace ids or correlation ids.
+
+Names and values are strings, so that any transport can carry them;
+the names of the common headers are provided as constants.
+*/
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Headers {
+    map: BTreeMap<String, String>,
+}
+
+impl Headers {
+    /// Id to correlate the call with its response.
+    pub const CORRELATION_ID: &'static str = "correlation-id";
+    /// Identity of the sender of the call.
+    pub const SENDER: &'static str = "sender";
+    /// When the call is sent.
+    pub const TIMESTAMP: &'static str = "timestamp";
+    /// Id of the trace the call belongs to.
+    pub const TRACE_ID: &'static str = "trace-id";
+
+    /// Create empty headers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Amount of the headers.
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Check if there's no header.
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Get the value of a header.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.map.get(name).map(String::as_str)
+    }
+
+    /// Set a header, returning its previous value.
+    pub fn insert(&mut self, name: impl Into<String>, value: impl Into<String>) -> Option<String> {
+        self.map.insert(name.into(), value.into())
+    }
+
+    /// Remove a header, returning its value.
+    pub fn remove(&mut self, name: &str) -> Option<String> {
+        self.map.remove(name)
+    }
+
+    /// Iterate over the headers, ordered by their names.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.map.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+}
+
+impl<K: Into<String>, V: Into<String>> FromIterator<(K, V)> for Headers {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut res = Self::new();
+        res.extend(iter);
+        res
+    }
+}
+
+impl<K: Into<String>, V: Into<String>> Extend<(K, V)> for Headers {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        self.map.extend(iter.into_iter().map(|(k, v)| (k.into(), v.into())));
+    }
+}
+
+impl IntoIterator for Headers {
+    type IntoIter = btree_map::IntoIter<String, String>;
+    type Item = (String, String);
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.map.into_iter()
+    }
+}
+
+
+
+/**
+A request with the headers of the call, carried from the client to the provider.
+
+A client stub wraps the request with [`Envelope::with_context`], so that the headers of the current call flow on,
+and the transport delivers the envelope as a whole;
+at the other end, [`Dispatcher::dispatch_envelope`](crate::Dispatcher::dispatch_envelope)
+makes the headers the [`CallContext`] of the provider while the request is dispatched.
+*/
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Envelope<Req> {
+    /// Headers of the call.
+    pub headers: Headers,
+    /// The request itself.
+    pub request: Req,
+}
+
+impl<Req> Envelope<Req> {
+    /// Wrap a request without any header.
+    pub fn new(request: Req) -> Self {
+        Self {
+            headers: Headers::new(),
+            request,
+        }
+    }
+
+    /// Wrap a request with the headers of the current [`CallContext`].
+    pub fn with_context(request: Req) -> Self {
+        Self {
+            headers: CallContext::current().headers().clone(),
+            request,
+        }
+    }
+
+    /// Set a header.
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.insert(name, value);
+        self
+    }
+
+    /// Convert the request, keeping the headers.
+    pub fn map<U>(self, f: impl FnOnce(Req) -> U) -> Envelope<U> {
+        Envelope {
+            headers: self.headers,
+            request: f(self.request),
+        }
+    }
+}
+
+
+
+thread_local! {
+    static CURRENT: RefCell<CallContext> = RefCell::default();
+}
+
+/**
+Context of the call being handled on the current thread, i.e. its headers and the peer calling.
+
+Providers read it by [`CallContext::current`] without any change to the signatures of their traits;
+it's set by [`CallContext::scope`], usually through [`Dispatcher::dispatch_envelope`](crate::Dispatcher::dispatch_envelope)
+or [`Dispatcher::dispatch_from`](crate::Dispatcher::dispatch_from) with the peer of the connection.
+It's empty outside of any call.
+
+```
+use frincoe_rpc::{CallContext, Dispatcher, Envelope, Headers, Peer};
+
+struct Echo;
+impl Dispatcher for Echo {
+    type Request = String;
+    type Response = String;
+    fn dispatch(&mut self, request: String) -> String {
+        let trace = CallContext::current().get(Headers::TRACE_ID).unwrap_or("none").to_string();
+        format!("{} in {}", request, trace)
+    }
+}
+
+let envelope = Envelope::new("hi".to_string()).header(Headers::TRACE_ID, "42");
+assert_eq!(Echo.dispatch_envelope(envelope), "hi in 42");
+assert_eq!(Echo.dispatch("hi".to_string()), "hi in none");
+
+let peer = Peer::Principal("alice".to_string());
+let context = CallContext::default().with_peer(Some(peer.clone()));
+assert_eq!(context.scope(|| CallContext::current().peer().cloned()), Some(peer));
+```
+*/
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CallContext {
+    headers: Arc<Headers>,
+    peer: Option<Arc<Peer>>,
+}
+
+impl CallContext {
+    /// Create a context with the headers.
+    pub fn new(headers: Headers) -> Self {
+        Self {
+            headers: Arc::new(headers),
+            peer: None,
+        }
+    }
+
+    /// Set the peer calling.
+    pub fn with_peer(mut self, peer: Option<Peer>) -> Self {
+        self.peer = peer.map(Arc::new);
+        self
+    }
+
+    /// The context of the call being handled on the current thread.
+    pub fn current() -> Self {
+        CURRENT.with(|x| x.borrow().clone())
+    }
+
+    /// Headers of the call.
+    pub fn headers(&self) -> &Headers {
+        &self.headers
+    }
+
+    /// Get the value of a header of the call.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.headers.get(name)
+    }
+
+    /// The peer calling, or `None` if it's anonymous or the call is local.
+    pub fn peer(&self) -> Option<&Peer> {
+        self.peer.as_deref()
+    }
+
+    /// Run the closure with this context as the current one, e.g. to carry it to another thread.
+    pub fn scope<R>(&self, f: impl FnOnce() -> R) -> R {
+        /// Restore the previous context, even if the closure panics.
+        struct Restore(Option<CallContext>);
+
+        impl Drop for Restore {
+            fn drop(&mut self) {
+                if let Some(previous) = self.0.take() {
+                    CURRENT.with(|x| *x.borrow_mut() = previous);
+                }
+            }
+        }
+
+        let _restore = Restore(Some(CURRENT.with(|x| x.replace(self.clone()))));
+        f()
+    }
+}
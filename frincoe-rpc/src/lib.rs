@@ -74,7 +74,16 @@ assert_eq!(handle.hello("blah"), "hi blah");
 
 
 
+use core::ops::{Deref, DerefMut};
 use core::task::Poll;
+use std::rc::Rc;
+use std::sync::Arc;
+
+mod envelope;
+pub use self::envelope::{CallContext, Envelope, Headers};
+
+mod peer;
+pub use self::peer::{Identified, Peer};
 
 
 
@@ -93,14 +102,180 @@ pub trait Connection {
     fn disconnect(&self) -> Result<(), Self::Error>;
 }
 
+impl<T: Connection + ?Sized> Connection for &T {
+    type Error = T::Error;
+
+    fn disconnect(&self) -> Result<(), Self::Error> {
+        (**self).disconnect()
+    }
+}
+
+impl<T: Connection + ?Sized> Connection for &mut T {
+    type Error = T::Error;
+
+    fn disconnect(&self) -> Result<(), Self::Error> {
+        (**self).disconnect()
+    }
+}
+
+impl<T: Connection + ?Sized> Connection for Box<T> {
+    type Error = T::Error;
+
+    fn disconnect(&self) -> Result<(), Self::Error> {
+        (**self).disconnect()
+    }
+}
+
+impl<T: Connection + ?Sized> Connection for Rc<T> {
+    type Error = T::Error;
+
+    fn disconnect(&self) -> Result<(), Self::Error> {
+        (**self).disconnect()
+    }
+}
+
+impl<T: Connection + ?Sized> Connection for Arc<T> {
+    type Error = T::Error;
+
+    fn disconnect(&self) -> Result<(), Self::Error> {
+        (**self).disconnect()
+    }
+}
+
+/// Type-erased error of a [`DynConnection`].
+pub type DynError = Box<dyn std::error::Error + Send + Sync>;
+
+/**
+Object-safe companion of [`Connection`], with the error type erased into [`DynError`].
+
+It's implemented for all connections whose errors can be boxed,
+so that connections of different types can be stored together as `Box<dyn DynConnection>`,
+which is a [`Connection`] again.
+
+```
+use std::convert::Infallible;
+use frincoe_rpc::{Connection, DynConnection};
+
+struct Conn;
+impl Connection for Conn {
+    type Error = Infallible;
+    fn disconnect(&self) -> Result<(), Self::Error> { Ok(()) }
+}
+
+let conns: Vec<Box<dyn DynConnection>> = vec![Box::new(Conn), Box::new(Box::new(Conn))];
+assert!(conns.iter().all(|x| x.disconnect().is_ok()));
+```
+*/
+pub trait DynConnection {
+    /// Disconnect from another end of the connection, see [`Connection::disconnect`].
+    fn disconnect_dyn(&self) -> Result<(), DynError>;
+}
+
+impl<T: Connection> DynConnection for T
+where
+    T::Error: Into<DynError>,
+{
+    fn disconnect_dyn(&self) -> Result<(), DynError> {
+        self.disconnect().map_err(Into::into)
+    }
+}
+
+impl Connection for dyn DynConnection + '_ {
+    type Error = DynError;
+
+    fn disconnect(&self) -> Result<(), Self::Error> {
+        self.disconnect_dyn()
+    }
+}
+
+/**
+Disconnect the connection inside when dropped, so that it can't be leaked.
+
+Errors of the disconnection on dropping are ignored,
+use [`DisconnectGuard::disconnect`] to disconnect manually and check the result.
+
+```
+use std::cell::Cell;
+use frincoe_rpc::{Connection, DisconnectGuard};
+
+struct Conn<'a>(&'a Cell<bool>);
+impl Connection for Conn<'_> {
+    type Error = ();
+    fn disconnect(&self) -> Result<(), Self::Error> {
+        self.0.set(true);
+        Ok(())
+    }
+}
+
+let closed = Cell::new(false);
+{
+    let _guard = DisconnectGuard::new(Conn(&closed));
+    assert!(!closed.get());
+}
+assert!(closed.get());
+```
+*/
+#[derive(Debug)]
+pub struct DisconnectGuard<C: Connection> {
+    conn: Option<C>,
+}
+
+impl<C: Connection> DisconnectGuard<C> {
+    /// Guard the connection.
+    pub fn new(conn: C) -> Self {
+        Self { conn: Some(conn) }
+    }
+
+    /// Disconnect now, returning the result of the disconnection.
+    pub fn disconnect(mut self) -> Result<(), C::Error> {
+        self.conn
+            .take()
+            .expect("the connection is only taken when consuming the guard")
+            .disconnect()
+    }
+
+    /// Release the connection without disconnecting it.
+    pub fn into_inner(mut self) -> C {
+        self.conn
+            .take()
+            .expect("the connection is only taken when consuming the guard")
+    }
+}
+
+impl<C: Connection> Deref for DisconnectGuard<C> {
+    type Target = C;
+
+    fn deref(&self) -> &Self::Target {
+        self.conn
+            .as_ref()
+            .expect("the connection is only taken when consuming the guard")
+    }
+}
+
+impl<C: Connection> DerefMut for DisconnectGuard<C> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.conn
+            .as_mut()
+            .expect("the connection is only taken when consuming the guard")
+    }
+}
+
+impl<C: Connection> Drop for DisconnectGuard<C> {
+    fn drop(&mut self) {
+        if let Some(conn) = self.conn.take() {
+            let _ = conn.disconnect();
+        }
+    }
+}
+
 /**
 Serve on some address to accept incoming connections, used for active providers.
 */
 pub trait Server: Sized {
     /// The address to listen on
     type Address;
-    /// The type of an incoming client
-    type Incoming: Connection;
+    /// The type of an incoming client, knowing who the client is
+    type Incoming: Identified;
     /// Possible errors during serving
     type Error;
     fn serve(addr: Self::Address) -> Result<Self, Self::Error>;
@@ -139,6 +314,20 @@ pub trait Dispatcher {
     /// Dispatch the request to functions according to their types,
     /// returning respective response.
     fn dispatch(&mut self, request: Self::Request) -> Self::Response;
+    /// Dispatch the request in an envelope,
+    /// with its headers as the [`CallContext`] of the providers during the dispatch.
+    fn dispatch_envelope(&mut self, envelope: Envelope<Self::Request>) -> Self::Response {
+        let Envelope { headers, request } = envelope;
+        CallContext::new(headers).scope(|| self.dispatch(request))
+    }
+    /// Dispatch the request in an envelope from a peer, usually [`Identified::peer`] of the connection,
+    /// with its headers and the peer as the [`CallContext`] of the providers during the dispatch.
+    fn dispatch_from(&mut self, peer: Option<Peer>, envelope: Envelope<Self::Request>) -> Self::Response {
+        let Envelope { headers, request } = envelope;
+        CallContext::new(headers)
+            .with_peer(peer)
+            .scope(|| self.dispatch(request))
+    }
 }
 
 
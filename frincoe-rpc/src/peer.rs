@@ -0,0 +1,93 @@
+use std::fmt::{self, Display};
+use std::net::SocketAddr;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use crate::Connection;
+
+
+
+/**
+Identity of the other end of a connection, as known by the transport.
+
+Unlike the [`Headers`](crate::Headers) sent by the clients, it's decided by the server accepting the connection,
+so providers can trust it as far as they trust the transport.
+*/
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Peer {
+    /// Address of a socket peer.
+    Address(SocketAddr),
+    /// Credentials of a Unix socket peer.
+    Unix {
+        /// Process id, if known.
+        pid: Option<u32>,
+        /// User id.
+        uid: u32,
+        /// Group id.
+        gid: u32,
+    },
+    /// A principal authenticated by the transport, e.g. the subject of a client certificate.
+    Principal(String),
+}
+
+impl Display for Peer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Address(addr) => write!(f, "{}", addr),
+            Self::Unix {
+                pid: Some(pid),
+                uid,
+                gid,
+            } => write!(f, "unix:{}:{}:{}", uid, gid, pid),
+            Self::Unix { pid: None, uid, gid } => write!(f, "unix:{}:{}", uid, gid),
+            Self::Principal(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+impl From<SocketAddr> for Peer {
+    fn from(addr: SocketAddr) -> Self {
+        Self::Address(addr)
+    }
+}
+
+/**
+Connection knowing the identity of its other end, required for connections accepted by [`Server`](crate::Server)s.
+
+The identity is passed to [`Dispatcher::dispatch_from`](crate::Dispatcher::dispatch_from)
+when dispatching the calls from the connection, so that providers can read it from the [`CallContext`](crate::CallContext).
+*/
+pub trait Identified: Connection {
+    /// Identity of the other end, or `None` if it's anonymous.
+    fn peer(&self) -> Option<Peer>;
+}
+
+impl<T: Identified + ?Sized> Identified for &T {
+    fn peer(&self) -> Option<Peer> {
+        (**self).peer()
+    }
+}
+
+impl<T: Identified + ?Sized> Identified for &mut T {
+    fn peer(&self) -> Option<Peer> {
+        (**self).peer()
+    }
+}
+
+impl<T: Identified + ?Sized> Identified for Box<T> {
+    fn peer(&self) -> Option<Peer> {
+        (**self).peer()
+    }
+}
+
+impl<T: Identified + ?Sized> Identified for Rc<T> {
+    fn peer(&self) -> Option<Peer> {
+        (**self).peer()
+    }
+}
+
+impl<T: Identified + ?Sized> Identified for Arc<T> {
+    fn peer(&self) -> Option<Peer> {
+        (**self).peer()
+    }
+}
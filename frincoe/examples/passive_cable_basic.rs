@@ -31,6 +31,14 @@ impl VaryGreet {
     }
 }
 
+impl Connection for VaryGreet {
+    type Error = ();
+
+    fn disconnect(&self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
 impl Greet for VaryGreet {
     fn hello(&mut self, name: &str) -> Bundle<String> {
         Bundle::from_single(format!("{} {}", self.hello, name))
@@ -103,9 +111,12 @@ fn main() {
     let mut counted = CountedGreet::new();
     cable.add_connection(&mut greet1).expect("Should be OK");
     cable.add_connection(&mut greet2).expect("Should be OK");
-    cable.add_connection(&mut counted).expect("Should be OK");
+    let counted_id = cable.add_connection(&mut counted).expect("Should be OK");
     assert_eq!(cable.hello("world"), ["h1 world", "h2 world", "hello #0 world"]);
     assert_eq!(cable.hello("nico"), ["h1 nico", "h2 nico", "hello #1 nico"]);
     assert_eq!(cable.bye("qwq"), ["bye qwq", "bye qwq", "bye #2 qwq"]);
-    cable.disconnect().expect("Should be OK");
+    // Remove a client by its id
+    cable.remove_connection(counted_id).expect("Should be present");
+    assert_eq!(cable.hello("again"), ["h1 again", "h2 again"]);
+    // The clients are plain trait objects rather than connections, so there's nothing to disconnect
 }
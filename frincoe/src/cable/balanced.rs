@@ -0,0 +1,538 @@
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::hash::{BuildHasher, Hasher};
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard};
+
+use frincoe_rpc::Connection;
+
+use super::slot_map::{SlotEntriesMut, SlotIterMut, SlotMap};
+use super::{Cable, ChildErrors, ConnectionId};
+
+
+
+/// A child of a [`BalancedCable`] to pick, with the amount of the calls to it not completed yet.
+#[derive(Debug)]
+pub struct Candidate {
+    id: ConnectionId,
+    outstanding: AtomicUsize,
+}
+
+impl Candidate {
+    fn new(id: ConnectionId) -> Self {
+        Self {
+            id,
+            outstanding: AtomicUsize::new(0),
+        }
+    }
+
+    /// Id of the child.
+    pub fn id(&self) -> ConnectionId {
+        self.id
+    }
+
+    /// Amount of the calls to the child not completed yet, including those from other threads.
+    pub fn outstanding(&self) -> usize {
+        self.outstanding.load(Ordering::Relaxed)
+    }
+}
+
+/**
+Policy of a [`BalancedCable`] deciding which child to call.
+
+Besides picking, the policy is notified when a child is removed,
+so that it can keep track of the children.
+*/
+pub trait Policy {
+    /// Pick one of the children, given in the order they're added,
+    /// returning the position of the picked one; `children` is never empty.
+    fn pick(&mut self, children: &[Candidate]) -> usize;
+    /// Notified when a child is removed.
+    fn remove(&mut self, _id: ConnectionId) {}
+}
+
+/// Pick the children in turn.
+#[derive(Clone, Default, Debug)]
+pub struct RoundRobin {
+    next: usize,
+}
+
+impl Policy for RoundRobin {
+    fn pick(&mut self, children: &[Candidate]) -> usize {
+        let index = self.next % children.len();
+        self.next = index + 1;
+        index
+    }
+}
+
+/// Pick the children randomly, reproducible with a given seed.
+#[derive(Clone, Debug)]
+pub struct Random {
+    state: u64,
+}
+
+impl Random {
+    /// Create a policy producing the same sequence of picks for the same seed.
+    pub fn with_seed(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    /// Next random number, by SplitMix64.
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+}
+
+impl Default for Random {
+    /// Create a policy with a random seed.
+    fn default() -> Self {
+        Self::with_seed(RandomState::new().build_hasher().finish())
+    }
+}
+
+impl Policy for Random {
+    fn pick(&mut self, children: &[Candidate]) -> usize {
+        ((self.next_u64() as u128 * children.len() as u128) >> 64) as usize
+    }
+}
+
+/**
+Pick the child with the least calls not completed yet, the earliest added one for ties.
+
+The calls are counted by the cable until the [`Picked`] children are dropped,
+so the load is only spread when the calls overlap,
+i.e. when the cable is called from several threads through methods taking `&self`.
+
+```
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Barrier;
+use std::thread;
+use frincoe::cable::{BalancedCable, Cable, LeastOutstanding};
+use frincoe_macros::{dispatch_one, inject_implement};
+# use frincoe_rpc::Connection;
+
+trait Work {
+    fn run(&self, job: u32) -> u32;
+}
+
+struct Worker<'a> {
+    calls: AtomicUsize,
+    barrier: &'a Barrier,
+}
+impl Work for Worker<'_> {
+    fn run(&self, job: u32) -> u32 {
+        self.calls.fetch_add(1, Ordering::Relaxed);
+        // Keep the call outstanding until all the calls are made
+        self.barrier.wait();
+        job
+    }
+}
+# impl Connection for Worker<'_> {
+#     type Error = ();
+#     fn disconnect(&self) -> Result<(), ()> { Ok(()) }
+# }
+
+inject_implement! {
+    impl {
+        trait Work {
+            fn run(&self, job: u32) -> u32;
+        }
+    } for BalancedCable<Worker<'_>, LeastOutstanding> in dispatch_one
+}
+
+let barrier = Barrier::new(3);
+let mut pool = BalancedCable::<_, LeastOutstanding>::new();
+for _ in 0..3 {
+    pool.add_connection(Worker { calls: AtomicUsize::new(0), barrier: &barrier }).unwrap();
+}
+thread::scope(|s| {
+    for job in 0..3 {
+        let pool = &pool;
+        s.spawn(move || pool.run(job));
+    }
+});
+// Each worker gets one of the overlapping calls
+assert!(pool.iter_child().all(|x| x.calls.load(Ordering::Relaxed) == 1));
+assert!(pool.candidates().iter().all(|x| x.outstanding() == 0));
+```
+*/
+#[derive(Clone, Copy, Default, Debug)]
+pub struct LeastOutstanding;
+
+impl Policy for LeastOutstanding {
+    fn pick(&mut self, children: &[Candidate]) -> usize {
+        let (index, _) = children
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, x)| x.outstanding())
+            .expect("there should be children to pick");
+        index
+    }
+}
+
+/**
+Pick the children in proportion to their weights, spreading the picks of a child evenly.
+
+Children have a weight of 1 unless set otherwise,
+and children with a weight of 0 are never picked unless all of them are.
+*/
+#[derive(Clone, Default, Debug)]
+pub struct Weighted {
+    weights: HashMap<ConnectionId, u32>,
+    /// Current weights of the smooth weighted round-robin.
+    current: HashMap<ConnectionId, i64>,
+}
+
+impl Weighted {
+    /// Weight of a child.
+    pub fn weight(&self, id: ConnectionId) -> u32 {
+        self.weights.get(&id).copied().unwrap_or(1)
+    }
+
+    /// Set the weight of a child.
+    pub fn set_weight(&mut self, id: ConnectionId, weight: u32) {
+        self.weights.insert(id, weight);
+    }
+}
+
+impl Policy for Weighted {
+    fn pick(&mut self, children: &[Candidate]) -> usize {
+        let mut total = 0;
+        let mut best = None;
+        for (index, child) in children.iter().enumerate() {
+            let weight = self.weight(child.id) as i64;
+            let current = self.current.entry(child.id).or_default();
+            *current += weight;
+            total += weight;
+            if best.is_none_or(|(_, max)| *current > max) {
+                best = Some((index, *current));
+            }
+        }
+        let (index, _) = best.expect("there should be children to pick");
+        *self.current.entry(children[index].id).or_default() -= total;
+        index
+    }
+
+    fn remove(&mut self, id: ConnectionId) {
+        self.weights.remove(&id);
+        self.current.remove(&id);
+    }
+}
+
+
+
+/**
+A child picked by a [`BalancedCable`], whose call is counted as outstanding until it's dropped.
+
+It dereferences to the child, mutably if it's picked by [`BalancedCable::pick_child`].
+*/
+#[derive(Debug)]
+pub struct Picked<'a, R> {
+    id: ConnectionId,
+    client: R,
+    outstanding: &'a AtomicUsize,
+}
+
+impl<R> Picked<'_, R> {
+    /// Id of the child.
+    pub fn id(&self) -> ConnectionId {
+        self.id
+    }
+}
+
+impl<R: Deref> Deref for Picked<'_, R> {
+    type Target = R::Target;
+
+    fn deref(&self) -> &Self::Target {
+        &self.client
+    }
+}
+
+impl<R: DerefMut> DerefMut for Picked<'_, R> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.client
+    }
+}
+
+impl<R> Drop for Picked<'_, R> {
+    /// Complete the call, also when the child panics.
+    fn drop(&mut self) {
+        self.outstanding.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Pick a candidate by the policy, counting the call to it.
+fn pick<'a>(policy: &mut impl Policy, children: &'a [Candidate]) -> Option<&'a Candidate> {
+    if children.is_empty() {
+        return None;
+    }
+    let picked = &children[policy.pick(children)];
+    picked.outstanding.fetch_add(1, Ordering::Relaxed);
+    Some(picked)
+}
+
+
+
+/**
+Cable calling exactly one of its children for each call, picked by a [`Policy`].
+
+The children are picked with [`BalancedCable::pick_child`], or [`BalancedCable::pick_shared`] through `&self`,
+and the calls to them are counted as outstanding until the [`Picked`] children are dropped.
+Use [`dispatch_one`](frincoe_macros::dispatch_one) to implement traits by calling a picked child;
+the cable is also a [`Cable`], so [`dispatch_sub`](frincoe_macros::dispatch_sub) still broadcasts to all the children.
+
+```
+use frincoe::cable::{BalancedCable, Bundle, Cable, RoundRobin};
+use frincoe_macros::{dispatch_one, inject_implement};
+# use frincoe_rpc::Connection;
+
+trait Work {
+    fn run(&mut self, job: u32) -> Bundle<String>;
+}
+
+struct Worker(&'static str);
+impl Work for Worker {
+    fn run(&mut self, job: u32) -> Bundle<String> {
+        Bundle::from_single(format!("{} runs {}", self.0, job))
+    }
+}
+# impl Connection for Worker {
+#     type Error = ();
+#     fn disconnect(&self) -> Result<(), ()> { Ok(()) }
+# }
+
+inject_implement! {
+    impl {
+        trait Work {
+            fn run(&mut self, job: u32) -> Bundle<String>;
+        }
+    } for BalancedCable<Worker, RoundRobin> in dispatch_one
+}
+
+let mut pool = BalancedCable::<Worker>::new();
+pool.add_connection(Worker("a")).unwrap();
+pool.add_connection(Worker("b")).unwrap();
+assert_eq!(pool.run(1), ["a runs 1"]);
+assert_eq!(pool.run(2), ["b runs 2"]);
+assert_eq!(pool.run(3), ["a runs 3"]);
+```
+*/
+#[derive(Debug)]
+pub struct BalancedCable<T, P = RoundRobin> {
+    child: SlotMap<T>,
+    /// The children in the order they're added.
+    ids: Vec<Candidate>,
+    policy: Mutex<P>,
+}
+
+impl<T: Clone, P: Clone> Clone for BalancedCable<T, P> {
+    /// Clone the children and the policy, without the calls not completed yet.
+    fn clone(&self) -> Self {
+        Self {
+            child: self.child.clone(),
+            ids: self.ids.iter().map(|x| Candidate::new(x.id)).collect(),
+            policy: Mutex::new(self.policy().clone()),
+        }
+    }
+}
+
+impl<T, P: Default> Default for BalancedCable<T, P> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, P: Default> BalancedCable<T, P> {
+    /// Create an empty BalancedCable
+    pub fn new() -> Self {
+        Self::with_policy(P::default())
+    }
+}
+
+impl<T, P> BalancedCable<T, P> {
+    /// Create an empty BalancedCable with the policy.
+    pub fn with_policy(policy: P) -> Self {
+        Self {
+            child: SlotMap::new(),
+            ids: vec![],
+            policy: Mutex::new(policy),
+        }
+    }
+
+    /// Amount of the clients.
+    pub fn len(&self) -> usize {
+        self.child.len()
+    }
+
+    /// Check if there's no client.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The policy picking the children, locked while it's borrowed.
+    pub fn policy(&self) -> MutexGuard<'_, P> {
+        lock(&self.policy)
+    }
+
+    /// The policy picking the children, e.g. to set the weights of [`Weighted`].
+    pub fn policy_mut(&mut self) -> &mut P {
+        self.policy.get_mut().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// The children in the order they're added, with their calls not completed yet.
+    pub fn candidates(&self) -> &[Candidate] {
+        &self.ids
+    }
+}
+
+impl<T, P: Policy> BalancedCable<T, P> {
+    /// Pick a child to call by the policy, or `None` if there's no child.
+    pub fn pick_child(&mut self) -> Option<Picked<'_, &mut T>> {
+        let policy = self.policy.get_mut().unwrap_or_else(|e| e.into_inner());
+        let picked = pick(policy, &self.ids)?;
+        Some(Picked {
+            id: picked.id,
+            client: self.child.get_mut(picked.id).expect("the candidates are the children"),
+            outstanding: &picked.outstanding,
+        })
+    }
+
+    /// Pick a child to call by the policy through `&self`, e.g. from several threads.
+    pub fn pick_shared(&self) -> Option<Picked<'_, &T>> {
+        let picked = pick(&mut *lock(&self.policy), &self.ids)?;
+        Some(Picked {
+            id: picked.id,
+            client: self.child.get(picked.id).expect("the candidates are the children"),
+            outstanding: &picked.outstanding,
+        })
+    }
+}
+
+impl<T: Connection, P> Connection for BalancedCable<T, P> {
+    type Error = ChildErrors<T::Error>;
+
+    fn disconnect(&self) -> Result<(), Self::Error> {
+        self.child
+            .iter()
+            .filter_map(|(id, x)| x.disconnect().err().map(|e| (id, e)))
+            .collect::<ChildErrors<_>>()
+            .into_result()
+    }
+}
+
+impl<T, P: Policy> Cable for BalancedCable<T, P> {
+    type AddError = Infallible;
+    type ChildIdIter<'a>
+        = SlotEntriesMut<'a, T>
+    where
+        Self: 'a;
+    type ChildIter<'a>
+        = SlotIterMut<'a, T>
+    where
+        Self: 'a;
+    type Client = T;
+
+    fn iter_child(&mut self) -> Self::ChildIter<'_> {
+        self.child.iter_mut()
+    }
+
+    fn iter_child_with_id(&mut self) -> Self::ChildIdIter<'_> {
+        self.child.entries_mut()
+    }
+
+    fn add_connection(&mut self, addr: Self::Client) -> Result<ConnectionId, Self::AddError> {
+        let id = self.child.insert(addr);
+        self.ids.push(Candidate::new(id));
+        Ok(id)
+    }
+
+    fn remove_connection(&mut self, id: ConnectionId) -> Option<Self::Client> {
+        let client = self.child.remove(id)?;
+        self.ids.retain(|x| x.id != id);
+        self.policy_mut().remove(id);
+        Some(client)
+    }
+
+    fn get_mut(&mut self, id: ConnectionId) -> Option<&mut Self::Client> {
+        self.child.get_mut(id)
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::panic::{self, AssertUnwindSafe};
+
+    use super::{BalancedCable, Candidate, LeastOutstanding, Policy, Random, RoundRobin, Weighted};
+    use crate::cable::{Cable, ConnectionId};
+
+    fn ids(n: u32) -> Vec<Candidate> {
+        (0..n).map(|x| Candidate::new(ConnectionId::positional(x))).collect()
+    }
+
+    fn picks(policy: &mut impl Policy, children: &[Candidate], n: usize) -> Vec<usize> {
+        (0..n).map(|_| policy.pick(children)).collect()
+    }
+
+    #[test]
+    fn round_robin() {
+        let mut policy = RoundRobin::default();
+        assert_eq!(picks(&mut policy, &ids(3), 5), [0, 1, 2, 0, 1]);
+        // Wraps when children are removed
+        assert_eq!(picks(&mut policy, &ids(2), 2), [0, 1]);
+    }
+
+    #[test]
+    fn random() {
+        let children = ids(4);
+        let a = picks(&mut Random::with_seed(42), &children, 100);
+        let b = picks(&mut Random::with_seed(42), &children, 100);
+        assert_eq!(a, b);
+        assert!((0..4).all(|i| a.contains(&i)));
+    }
+
+    #[test]
+    fn least_outstanding() {
+        let mut cable = BalancedCable::<u32, LeastOutstanding>::new();
+        let a = cable.add_connection(0).unwrap();
+        let b = cable.add_connection(1).unwrap();
+        {
+            let first = cable.pick_shared().unwrap();
+            let second = cable.pick_shared().unwrap();
+            let third = cable.pick_shared().unwrap();
+            assert_eq!([first.id(), second.id(), third.id()], [a, b, a]);
+            drop(first);
+            assert_eq!(cable.pick_shared().unwrap().id(), a);
+        }
+        // Calls complete when the picked children are dropped, also by panics
+        let panicked = panic::catch_unwind(AssertUnwindSafe(|| {
+            let mut picked = cable.pick_child().unwrap();
+            *picked += 1;
+            panic!("the child fails");
+        }));
+        assert!(panicked.is_err());
+        assert!(cable.candidates().iter().all(|x| x.outstanding() == 0));
+        assert_eq!(cable.get_mut(a), Some(&mut 1));
+    }
+
+    #[test]
+    fn weighted() {
+        let children = ids(3);
+        let mut policy = Weighted::default();
+        policy.set_weight(children[0].id(), 3);
+        policy.set_weight(children[2].id(), 0);
+        // Picks of the heavier child are spread
+        assert_eq!(picks(&mut policy, &children, 8), [0, 0, 1, 0, 0, 0, 1, 0]);
+    }
+}
@@ -0,0 +1,486 @@
+use std::collections::BTreeMap;
+use std::fmt::{self, Display};
+use std::ops::{Deref, DerefMut};
+
+use smallvec::SmallVec;
+
+use super::ConnectionId;
+
+
+
+/**
+The results of a cabled procedure.
+
+Due to some rust restrictions, this have to be a concrete type instead of a trait.
+
+Since a cable method should assembly all its children's results,
+and the signature of all these methods should be the same (well, constraintedly),
+all cable methods should return a `Bundle`.
+A better way is using a trait, so that some bundled values may have better optimization;
+but the solution using traits requires features that rust don't implement currently.
+
+Up to `N` items are stored inline, without allocating on the heap;
+by default, that's a single item, which is what a plain client returns.
+For cables broadcasting to a handful of children, use a larger `N` in the interface,
+e.g. `Bundle<String, 4>`, so that collecting their results doesn't allocate either.
+
+# Attribution
+
+A bundle may record which child of the cable produced each item, i.e. the source of the item,
+which is done by [`Bundle::extend_from`], e.g. by the `attributed` option of
+[`dispatch_sub`](frincoe_macros::dispatch_sub).
+Items added in other ways have no source.
+The sources are kept along with the items by the methods of the bundle,
+but reordering the items through the slice it dereferences to breaks the attribution.
+Attribution allocates a table for the sources on the heap,
+so bundles without any source cost nothing more.
+
+```
+use frincoe::cable::{ArrayCable, Bundle, Cable};
+use frincoe_macros::{dispatch_sub, inject_implement};
+# use frincoe_rpc::Connection;
+
+trait Answer {
+    fn answer(&mut self) -> Bundle<i32>;
+}
+
+struct Replier(Vec<i32>);
+# impl Connection for Replier {
+#     type Error = ();
+#     fn disconnect(&self) -> Result<(), ()> { Ok(()) }
+# }
+impl Answer for Replier {
+    fn answer(&mut self) -> Bundle<i32> {
+        self.0.iter().copied().collect()
+    }
+}
+
+inject_implement! {
+    impl {
+        trait Answer {
+            fn answer(&mut self) -> Bundle<i32>;
+        }
+    } for ArrayCable<Replier> in dispatch_sub(attributed)
+}
+
+let mut cable = ArrayCable::new();
+let a = cable.add_connection(Replier(vec![1, 2])).unwrap();
+let b = cable.add_connection(Replier(vec![3])).unwrap();
+let res = cable.answer();
+assert_eq!(res, [1, 2, 3]);
+assert_eq!(res.iter_from(a).collect::<Vec<_>>(), [&1, &2]);
+assert_eq!(res.source(2), Some(b));
+```
+*/
+#[derive(Clone, Debug)]
+pub struct Bundle<T, const N: usize = 1> {
+    items: SmallVec<[T; N]>,
+    /// Either empty if no item has a source, or of the same length as `items`.
+    sources: Vec<Option<ConnectionId>>,
+}
+
+impl<T, const N: usize> Bundle<T, N> {
+    /// Create an empty bundle.
+    pub fn new() -> Self {
+        Self {
+            items: SmallVec::new(),
+            sources: vec![],
+        }
+    }
+
+    /// Create a bundle with a single value
+    pub fn from_single(item: impl Into<T>) -> Self {
+        let mut res = Self::new();
+        res.items.push(item.into());
+        res
+    }
+
+    /// Append an item to the bundle.
+    pub fn push(&mut self, item: T) {
+        self.items.push(item);
+        if !self.sources.is_empty() {
+            self.sources.push(None);
+        }
+    }
+
+    /// Append the items produced by a child of a cable, recording the child as their source.
+    ///
+    /// The sources already recorded in `other` are replaced.
+    pub fn extend_from(&mut self, id: ConnectionId, other: Self) {
+        self.sources.resize(self.items.len(), None);
+        self.sources.extend(other.items.iter().map(|_| Some(id)));
+        self.items.extend(other.items);
+    }
+
+    /// Get the source of the item at the index, if there's the item and it has a source.
+    pub fn source(&self, index: usize) -> Option<ConnectionId> {
+        self.sources.get(index).copied().flatten()
+    }
+
+    /// Iterate over the items along with their sources.
+    pub fn iter_attributed(&self) -> impl Iterator<Item = (Option<ConnectionId>, &T)> {
+        self.items.iter().enumerate().map(|(i, x)| (self.source(i), x))
+    }
+
+    /// Iterate over the items produced by the given child.
+    pub fn iter_from(&self, id: ConnectionId) -> impl Iterator<Item = &T> {
+        self.iter_attributed()
+            .filter(move |(x, _)| *x == Some(id))
+            .map(|(_, x)| x)
+    }
+
+    /// Move out the items along with their sources.
+    pub fn into_attributed(self) -> impl Iterator<Item = (Option<ConnectionId>, T)> {
+        let sources = self.sources.into_iter().chain(std::iter::repeat(None));
+        sources.zip(self.items)
+    }
+
+    /// Group the items by their sources; items without a source are grouped under `None`.
+    ///
+    /// The groups are ordered by the ids, and the items in a group keep their order.
+    pub fn group_by_source(self) -> BTreeMap<Option<ConnectionId>, Self> {
+        let mut res = BTreeMap::<_, Self>::new();
+        for (source, item) in self.into_attributed() {
+            res.entry(source).or_default().push_from(source, item);
+        }
+        res
+    }
+
+    /// Append an item with the given source.
+    fn push_from(&mut self, source: Option<ConnectionId>, item: T) {
+        if source.is_some() || !self.sources.is_empty() {
+            self.sources.resize(self.items.len(), None);
+            self.sources.push(source);
+        }
+        self.items.push(item);
+    }
+
+    /// Check if the items have been moved to the heap, i.e. there are more than `N` items.
+    pub fn spilled(&self) -> bool {
+        self.items.spilled()
+    }
+
+    /// Convert into a `Vec`, without reallocating if the items are already on the heap.
+    pub fn into_vec(self) -> Vec<T> {
+        self.items.into_vec()
+    }
+}
+
+/// Error of [`Bundle::into_single`] when there's not exactly one item.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SingleError {
+    /// There's no item.
+    Empty,
+    /// There are more than one items, with the amount of them.
+    Multiple(usize),
+}
+
+impl Display for SingleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => f.write_str("expected a single item, but there's none"),
+            Self::Multiple(n) => write!(f, "expected a single item, but there are {}", n),
+        }
+    }
+}
+
+impl std::error::Error for SingleError {}
+
+/**
+Reductions of the results.
+
+Sources of the items are kept by the methods producing bundles,
+see also [attribution](Bundle#attribution).
+*/
+impl<T, const N: usize> Bundle<T, N> {
+    /// Take the first item.
+    ///
+    /// To borrow the first item instead, use `bundle.first()` of the slice.
+    pub fn into_first(self) -> Option<T> {
+        self.items.into_iter().next()
+    }
+
+    /// Take the only item, failing if there's no item or more than one items.
+    pub fn into_single(self) -> Result<T, SingleError> {
+        match self.items.len() {
+            1 => Ok(self.items.into_iter().next().expect("there's exactly one item")),
+            0 => Err(SingleError::Empty),
+            n => Err(SingleError::Multiple(n)),
+        }
+    }
+
+    /// Take the item equal to more than half of the items, if there's such one.
+    pub fn majority(self) -> Option<T>
+    where
+        T: PartialEq,
+    {
+        // Boyer-Moore majority vote, and then check if the candidate is really the majority
+        let mut candidate = None;
+        let mut count = 0usize;
+        for item in &self.items {
+            if count == 0 {
+                candidate = Some(item);
+            }
+            if candidate == Some(item) {
+                count += 1;
+            } else {
+                count -= 1;
+            }
+        }
+        let candidate = candidate?;
+        let total = self.items.iter().filter(|x| *x == candidate).count();
+        if total * 2 > self.items.len() {
+            let index = self
+                .items
+                .iter()
+                .position(|x| x == candidate)
+                .expect("the candidate is an item");
+            self.items.into_iter().nth(index)
+        } else {
+            None
+        }
+    }
+
+    /// Merge all the items into one with `f` from the first one, or `None` if there's no item.
+    pub fn merge_with(self, f: impl FnMut(T, T) -> T) -> Option<T> {
+        self.items.into_iter().reduce(f)
+    }
+
+    /// Flatten the items which are collections themselves,
+    /// every item inherits the source of the collection containing it.
+    pub fn flatten(self) -> Bundle<T::Item, N>
+    where
+        T: IntoIterator,
+    {
+        let mut res = Bundle::new();
+        for (source, items) in self.into_attributed() {
+            for item in items {
+                res.push_from(source, item);
+            }
+        }
+        res
+    }
+}
+
+impl<T, E, const N: usize> Bundle<Result<T, E>, N> {
+    /// Take out the successful values, or the first error if there's any.
+    pub fn transpose(self) -> Result<Bundle<T, N>, E> {
+        let mut res = Bundle::new();
+        for (source, item) in self.into_attributed() {
+            res.push_from(source, item?);
+        }
+        Ok(res)
+    }
+
+    /// Split into the successful values and the errors.
+    pub fn partition_results(self) -> (Bundle<T, N>, Bundle<E, N>) {
+        let mut values = Bundle::new();
+        let mut errors = Bundle::new();
+        for (source, item) in self.into_attributed() {
+            match item {
+                Ok(value) => values.push_from(source, value),
+                Err(error) => errors.push_from(source, error),
+            }
+        }
+        (values, errors)
+    }
+}
+
+impl<T, const N: usize> Default for Bundle<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, U: Into<T>, const N: usize> FromIterator<U> for Bundle<T, N> {
+    fn from_iter<R: IntoIterator<Item = U>>(iter: R) -> Self {
+        Self {
+            items: iter.into_iter().map(Into::<T>::into).collect(),
+            sources: vec![],
+        }
+    }
+}
+
+impl<T, const N: usize> Extend<Bundle<T, N>> for Bundle<T, N> {
+    fn extend<R: IntoIterator<Item = Bundle<T, N>>>(&mut self, iter: R) {
+        for item in iter {
+            self.extend_one(item);
+        }
+    }
+
+    fn extend_one(&mut self, item: Bundle<T, N>) {
+        if !self.sources.is_empty() || !item.sources.is_empty() {
+            self.sources.resize(self.items.len(), None);
+            self.sources.extend(item.sources);
+            self.sources.resize(self.items.len() + item.items.len(), None);
+        }
+        self.items.extend(item.items)
+    }
+
+    fn extend_reserve(&mut self, additional: usize) {
+        self.items.reserve(additional);
+    }
+}
+
+impl<T, const N: usize> Extend<T> for Bundle<T, N> {
+    fn extend<R: IntoIterator<Item = T>>(&mut self, iter: R) {
+        self.items.extend(iter);
+        if !self.sources.is_empty() {
+            self.sources.resize(self.items.len(), None);
+        }
+    }
+
+    fn extend_one(&mut self, item: T) {
+        self.push(item);
+    }
+
+    fn extend_reserve(&mut self, additional: usize) {
+        self.items.reserve(additional);
+    }
+}
+
+impl<T, const N: usize> IntoIterator for Bundle<T, N> {
+    type IntoIter = smallvec::IntoIter<[T; N]>;
+    type Item = T;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.into_iter()
+    }
+}
+
+impl<T, const N: usize> Deref for Bundle<T, N> {
+    type Target = [T];
+
+    fn deref(&self) -> &Self::Target {
+        &self.items
+    }
+}
+
+impl<T, const N: usize> DerefMut for Bundle<T, N> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.items
+    }
+}
+
+impl<U, T: PartialEq<U>, const N: usize, const M: usize> PartialEq<[U; M]> for Bundle<T, N> {
+    fn eq(&self, other: &[U; M]) -> bool {
+        self.items[..] == other[..]
+    }
+}
+
+impl<U, T: PartialEq<U>, const N: usize> PartialEq<[U]> for Bundle<T, N> {
+    fn eq(&self, other: &[U]) -> bool {
+        self.items[..] == *other
+    }
+}
+
+impl<U, T: PartialEq<U>, const N: usize> PartialEq<&[U]> for Bundle<T, N> {
+    fn eq(&self, other: &&[U]) -> bool {
+        self.items[..] == **other
+    }
+}
+
+impl<U, T: PartialEq<U>, const N: usize> PartialEq<&mut [U]> for Bundle<T, N> {
+    fn eq(&self, other: &&mut [U]) -> bool {
+        self.items[..] == **other
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::{Bundle, SingleError};
+    use crate::cable::ConnectionId;
+
+    #[test]
+    fn inline() {
+        let mut single = Bundle::<String>::new();
+        single.extend(Bundle::<String>::from_single("a"));
+        assert!(!single.spilled());
+        single.extend(Bundle::<String>::from_single("b"));
+        assert!(single.spilled());
+        assert_eq!(single, ["a", "b"]);
+        let mut res = Bundle::<i32, 3>::new();
+        for i in 0..3 {
+            res.extend(Bundle::<i32, 3>::from_single(i));
+        }
+        assert!(!res.spilled());
+        assert_eq!(res, [0, 1, 2]);
+        res.push(3);
+        assert!(res.spilled());
+        assert_eq!(res.into_vec(), [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn attribution() {
+        let (a, b) = (ConnectionId::positional(0), ConnectionId::positional(1));
+        let mut res = Bundle::<i32>::from_single(0);
+        res.extend_from(a, [1, 2].into_iter().collect());
+        res.extend_from(b, Bundle::from_single(3));
+        res.push(4);
+        res.extend(Bundle::<i32>::from_single(5));
+        assert_eq!(res, [0, 1, 2, 3, 4, 5]);
+        assert_eq!(
+            res.iter_attributed().map(|(x, _)| x).collect::<Vec<_>>(),
+            [None, Some(a), Some(a), Some(b), None, None]
+        );
+        assert_eq!(res.iter_from(a).copied().collect::<Vec<_>>(), [1, 2]);
+        assert_eq!(res.source(3), Some(b));
+        assert_eq!(res.source(6), None);
+        // Sources are kept when the bundle is extended into another one
+        let mut outer = Bundle::<i32>::from_single(-1);
+        outer.extend([res]);
+        let groups = outer.group_by_source();
+        assert_eq!(groups.keys().copied().collect::<Vec<_>>(), [None, Some(a), Some(b)]);
+        assert_eq!(groups[&None], [-1, 0, 4, 5]);
+        assert_eq!(groups[&Some(a)], [1, 2]);
+        assert_eq!(groups[&Some(b)].source(0), Some(b));
+    }
+
+    #[test]
+    fn reductions() {
+        let bundle = |x: &[i32]| x.iter().copied().collect::<Bundle<i32>>();
+        assert_eq!(bundle(&[1, 2]).first(), Some(&1));
+        assert_eq!(bundle(&[1, 2]).into_first(), Some(1));
+        assert_eq!(bundle(&[]).into_first(), None);
+        assert_eq!(bundle(&[1]).into_single(), Ok(1));
+        assert_eq!(bundle(&[]).into_single(), Err(SingleError::Empty));
+        assert_eq!(bundle(&[1, 2, 3]).into_single(), Err(SingleError::Multiple(3)));
+        assert_eq!(bundle(&[1, 2, 1, 3, 1]).majority(), Some(1));
+        assert_eq!(bundle(&[2, 1, 1, 3]).majority(), None);
+        assert_eq!(bundle(&[3, 1, 3]).majority(), Some(3));
+        assert_eq!(bundle(&[]).majority(), None);
+        assert_eq!(bundle(&[1, 2, 3]).merge_with(|x, y| x + y), Some(6));
+        assert_eq!(bundle(&[]).merge_with(|x, y| x + y), None);
+    }
+
+    #[test]
+    fn reductions_keep_sources() {
+        let (a, b) = (ConnectionId::positional(0), ConnectionId::positional(1));
+        let mut res = Bundle::<Result<i32, String>>::new();
+        res.extend_from(a, [Ok(1), Err("a".to_string())].into_iter().collect());
+        res.extend_from(b, Bundle::from_single(Ok(2)));
+        let (values, errors) = res.clone().partition_results();
+        assert_eq!(values, [1, 2]);
+        assert_eq!((values.source(0), values.source(1)), (Some(a), Some(b)));
+        assert_eq!(errors, ["a"]);
+        assert_eq!(errors.source(0), Some(a));
+        assert_eq!(res.transpose().unwrap_err(), "a");
+        let mut res = Bundle::<Result<i32, String>>::from_single(Ok(0));
+        res.extend_from(b, Bundle::from_single(Ok(2)));
+        let res = res.transpose().unwrap();
+        assert_eq!(res, [0, 2]);
+        assert_eq!((res.source(0), res.source(1)), (None, Some(b)));
+        // Flattening
+        let mut res = Bundle::<Vec<i32>>::from_single(vec![0]);
+        res.extend_from(a, Bundle::from_single(vec![1, 2]));
+        res.extend_from(b, Bundle::from_single(vec![]));
+        let res = res.flatten();
+        assert_eq!(res, [0, 1, 2]);
+        assert_eq!(
+            res.iter_attributed().map(|(x, _)| x).collect::<Vec<_>>(),
+            [None, Some(a), Some(a)]
+        );
+    }
+}
@@ -0,0 +1,310 @@
+use std::fmt::{self, Display};
+
+use frincoe_rpc::Connection;
+
+use super::{Bundle, Cable, ConnectionId};
+
+
+
+/// Error of a call without any recipient, returned by the [`DeadLetterPolicy::Error`] policy.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Undelivered {
+    method: &'static str,
+}
+
+impl Undelivered {
+    /// Create the error of a call to the method.
+    pub fn new(method: &'static str) -> Self {
+        Self { method }
+    }
+
+    /// Name of the method called.
+    pub fn method(&self) -> &'static str {
+        self.method
+    }
+}
+
+impl Display for Undelivered {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "no recipient of the call to `{}`", self.method)
+    }
+}
+
+impl std::error::Error for Undelivered {}
+
+/// Results able to hold an [`Undelivered`] error, used by [`dispatch_sub`](frincoe_macros::dispatch_sub) to return it.
+pub trait Undeliverable {
+    /// The result of a call without any recipient.
+    fn undelivered(error: Undelivered) -> Self;
+}
+
+impl<T, E: From<Undelivered>, const N: usize> Undeliverable for Bundle<Result<T, E>, N> {
+    fn undelivered(error: Undelivered) -> Self {
+        Bundle::from_single(Err(error.into()))
+    }
+}
+
+impl<T, E: From<Undelivered>> Undeliverable for Vec<Result<T, E>> {
+    fn undelivered(error: Undelivered) -> Self {
+        vec![Err(error.into())]
+    }
+}
+
+
+
+/// What a [`DeadLetterCable`] does with a call without any recipient.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum DeadLetterPolicy<S> {
+    /// Drop the call and return `Default::default()`, as if there were no policy.
+    #[default]
+    Drop,
+    /// Return an [`Undelivered`] error,
+    /// which is kept for [`DeadLetterCable::take_unreported`] for methods without results.
+    Error,
+    /// Panic in debug builds, and drop the call in release builds.
+    DebugPanic,
+    /// Forward the call to the sink client, e.g. one recording the calls for later inspection or replay.
+    Forward(S),
+}
+
+/// Decision of a [`DeadLetterCable`] on a call without any recipient, used by [`dispatch_sub`](frincoe_macros::dispatch_sub).
+#[derive(Debug)]
+pub enum DeadLetter<'a, S> {
+    /// Drop the call.
+    Drop,
+    /// Return the error.
+    Error(Undelivered),
+    /// Call the sink instead.
+    Forward(&'a mut S),
+}
+
+/**
+Cable wrapping another [`Cable`] to handle the calls without any recipient by a [`DeadLetterPolicy`].
+
+By default, a call to a cable without children silently returns `Default::default()`,
+which loses the events that must be handled.
+Use the `dead_letter` option of [`dispatch_sub`](frincoe_macros::dispatch_sub) to implement traits,
+so that such calls are handed to [`DeadLetterCable::dead_letter`] and handled by the policy;
+the amount of them is counted by [`DeadLetterCable::undelivered`] whatever the policy is.
+Methods without results can't return the errors of [`DeadLetterPolicy::Error`],
+so the last one is kept until taken by [`DeadLetterCable::take_unreported`].
+
+```
+use frincoe::cable::{ArrayCable, Cable, DeadLetterCable, DeadLetterPolicy};
+use frincoe_macros::{dispatch_sub, inject_implement};
+# use frincoe_rpc::Connection;
+
+trait Events {
+    fn order_placed(&mut self, id: u32, amount: u64);
+}
+
+struct Billing;
+impl Events for Billing {
+    fn order_placed(&mut self, _: u32, _: u64) {}
+}
+# impl Connection for Billing {
+#     type Error = ();
+#     fn disconnect(&self) -> Result<(), ()> { Ok(()) }
+# }
+
+/// Record the calls to replay them later.
+#[derive(Default)]
+struct Letters(Vec<(&'static str, u32, u64)>);
+impl Events for Letters {
+    fn order_placed(&mut self, id: u32, amount: u64) {
+        self.0.push(("order_placed", id, amount));
+    }
+}
+
+inject_implement! {
+    impl {
+        trait Events {
+            fn order_placed(&mut self, id: u32, amount: u64);
+        }
+    } for DeadLetterCable<ArrayCable<Billing>, Letters> in dispatch_sub(dead_letter)
+}
+
+let mut cable = DeadLetterCable::with_policy(ArrayCable::new(), DeadLetterPolicy::Forward(Letters::default()));
+cable.order_placed(1, 42);
+cable.add_connection(Billing).unwrap();
+cable.order_placed(2, 7);
+assert_eq!(cable.undelivered(), 1);
+match cable.policy() {
+    DeadLetterPolicy::Forward(letters) => assert_eq!(letters.0, [("order_placed", 1, 42)]),
+    _ => unreachable!(),
+}
+
+let mut cable: DeadLetterCable<_, Letters> = DeadLetterCable::with_policy(ArrayCable::new(), DeadLetterPolicy::Error);
+cable.order_placed(3, 1);
+assert_eq!(cable.unreported(), 1);
+assert_eq!(cable.take_unreported().map(|e| e.method()), Some("order_placed"));
+assert_eq!(cable.take_unreported(), None);
+```
+*/
+#[derive(Clone, Debug, Default)]
+pub struct DeadLetterCable<C, S = ()> {
+    cable: C,
+    policy: DeadLetterPolicy<S>,
+    undelivered: u64,
+    unreported: u64,
+    /// The last error of a method without results, not taken yet.
+    last_unreported: Option<Undelivered>,
+}
+
+impl<C, S> DeadLetterCable<C, S> {
+    /// Wrap a cable, dropping the calls without any recipient.
+    pub fn new(cable: C) -> Self {
+        Self::with_policy(cable, DeadLetterPolicy::Drop)
+    }
+
+    /// Wrap a cable, handling the calls without any recipient by the policy.
+    pub fn with_policy(cable: C, policy: DeadLetterPolicy<S>) -> Self {
+        Self {
+            cable,
+            policy,
+            undelivered: 0,
+            unreported: 0,
+            last_unreported: None,
+        }
+    }
+
+    /// The policy handling the calls without any recipient.
+    pub fn policy(&self) -> &DeadLetterPolicy<S> {
+        &self.policy
+    }
+
+    /// The policy handling the calls without any recipient, e.g. to take the calls recorded by the sink.
+    pub fn policy_mut(&mut self) -> &mut DeadLetterPolicy<S> {
+        &mut self.policy
+    }
+
+    /// Amount of the calls without any recipient so far.
+    pub fn undelivered(&self) -> u64 {
+        self.undelivered
+    }
+
+    /// Amount of the errors of methods without results so far, which can't be returned.
+    pub fn unreported(&self) -> u64 {
+        self.unreported
+    }
+
+    /// Take the last error of a method without results, if there's any since the last time.
+    pub fn take_unreported(&mut self) -> Option<Undelivered> {
+        self.last_unreported.take()
+    }
+
+    /// Keep the error of a method without results, which can't be returned.
+    pub fn report_undelivered(&mut self, error: Undelivered) {
+        self.unreported += 1;
+        self.last_unreported = Some(error);
+    }
+
+    /// Handle a call to the method without any recipient by the policy.
+    pub fn dead_letter(&mut self, method: &'static str) -> DeadLetter<'_, S> {
+        self.undelivered += 1;
+        match &mut self.policy {
+            DeadLetterPolicy::Drop => DeadLetter::Drop,
+            DeadLetterPolicy::Error => DeadLetter::Error(Undelivered::new(method)),
+            DeadLetterPolicy::DebugPanic => {
+                debug_assert!(false, "{}", Undelivered::new(method));
+                DeadLetter::Drop
+            }
+            DeadLetterPolicy::Forward(sink) => DeadLetter::Forward(sink),
+        }
+    }
+
+    /// The wrapped cable.
+    pub fn inner(&self) -> &C {
+        &self.cable
+    }
+
+    /// The wrapped cable.
+    pub fn inner_mut(&mut self) -> &mut C {
+        &mut self.cable
+    }
+
+    /// Unwrap the cable, dropping the policy.
+    pub fn into_inner(self) -> C {
+        self.cable
+    }
+}
+
+impl<C: Connection, S> Connection for DeadLetterCable<C, S> {
+    type Error = C::Error;
+
+    /// Disconnect the children, the sink is not a child so it's left connected.
+    fn disconnect(&self) -> Result<(), Self::Error> {
+        self.cable.disconnect()
+    }
+}
+
+impl<C: Cable, S> Cable for DeadLetterCable<C, S> {
+    type AddError = C::AddError;
+    type ChildIdIter<'a>
+        = C::ChildIdIter<'a>
+    where
+        Self: 'a;
+    type ChildIter<'a>
+        = C::ChildIter<'a>
+    where
+        Self: 'a;
+    type Client = C::Client;
+
+    fn iter_child(&mut self) -> Self::ChildIter<'_> {
+        self.cable.iter_child()
+    }
+
+    fn iter_child_with_id(&mut self) -> Self::ChildIdIter<'_> {
+        self.cable.iter_child_with_id()
+    }
+
+    fn add_connection(&mut self, addr: Self::Client) -> Result<ConnectionId, Self::AddError> {
+        self.cable.add_connection(addr)
+    }
+
+    fn remove_connection(&mut self, id: ConnectionId) -> Option<Self::Client> {
+        self.cable.remove_connection(id)
+    }
+
+    fn get_mut(&mut self, id: ConnectionId) -> Option<&mut Self::Client> {
+        self.cable.get_mut(id)
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::{DeadLetter, DeadLetterCable, DeadLetterPolicy, Undeliverable, Undelivered};
+    use crate::cable::{ArrayCable, Bundle};
+
+    #[test]
+    fn policies() {
+        let mut cable = DeadLetterCable::<ArrayCable<()>, u32>::new(ArrayCable::new());
+        assert!(matches!(cable.dead_letter("f"), DeadLetter::Drop));
+        *cable.policy_mut() = DeadLetterPolicy::Error;
+        let error = match cable.dead_letter("f") {
+            DeadLetter::Error(e) => e,
+            _ => unreachable!(),
+        };
+        assert_eq!(error.method(), "f");
+        assert_eq!(
+            Bundle::<Result<(), Undelivered>>::undelivered(error),
+            [Err(Undelivered::new("f"))]
+        );
+        *cable.policy_mut() = DeadLetterPolicy::Forward(0);
+        if let DeadLetter::Forward(sink) = cable.dead_letter("f") {
+            *sink += 1;
+        }
+        assert_eq!(*cable.policy(), DeadLetterPolicy::Forward(1));
+        assert_eq!(cable.undelivered(), 3);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "no recipient of the call to `f`")]
+    fn debug_panic() {
+        DeadLetterCable::<ArrayCable<()>>::with_policy(ArrayCable::new(), DeadLetterPolicy::DebugPanic)
+            .dead_letter("f");
+    }
+}
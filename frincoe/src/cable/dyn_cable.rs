@@ -0,0 +1,104 @@
+use frincoe_rpc::{Connection, DynConnection, DynError};
+
+use super::{Cable, ConnectionId};
+
+
+
+/**
+Object-safe companion of [`Cable`], for cables chosen at runtime.
+
+It's implemented for all cables whose errors can be boxed,
+the children are iterated through boxed iterators and the errors are erased into [`DynError`],
+so that cables of different types can be used as `Box<dyn DynCable<C>>`,
+which is a [`Connection`] and thus can be nested in another cable.
+Use the `dynamic` option of [`dispatch_sub`](frincoe_macros::dispatch_sub)
+to forward calls through the trait object.
+
+```
+use std::convert::Infallible;
+use frincoe::cable::{ArrayCable, Bundle, DynCable, StaticCable};
+use frincoe_macros::{dispatch_sub, inject_implement};
+use frincoe_rpc::Connection;
+
+trait Greet {
+    fn hello(&mut self, name: &str) -> Bundle<String>;
+}
+
+#[derive(Debug)]
+struct English;
+impl Greet for English {
+    fn hello(&mut self, name: &str) -> Bundle<String> {
+        Bundle::from_single(format!("hello {}", name))
+    }
+}
+impl Connection for English {
+    type Error = Infallible;
+    fn disconnect(&self) -> Result<(), Self::Error> { Ok(()) }
+}
+
+inject_implement! {
+    impl {
+        trait Greet {
+            fn hello(&mut self, name: &str) -> Bundle<String>;
+        }
+    } for Box<dyn DynCable<English>> in dispatch_sub(dynamic)
+}
+
+let small = true;
+let mut cable: Box<dyn DynCable<English>> = if small {
+    Box::new(StaticCable::<English, 2>::new())
+} else {
+    Box::new(ArrayCable::<English>::new())
+};
+cable.add_connection_dyn(English).unwrap();
+cable.add_connection_dyn(English).unwrap();
+assert_eq!(cable.hello("world"), ["hello world", "hello world"]);
+assert!(cable.disconnect().is_ok());
+```
+*/
+pub trait DynCable<C>: DynConnection {
+    /// Returns an iterator over the children, see [`Cable::iter_child`].
+    fn iter_child_dyn(&mut self) -> Box<dyn Iterator<Item = &mut C> + '_>;
+    /// Returns an iterator over the children and their ids, see [`Cable::iter_child_with_id`].
+    fn iter_child_with_id_dyn(&mut self) -> Box<dyn Iterator<Item = (ConnectionId, &mut C)> + '_>;
+    /// Add a connection to the cable, see [`Cable::add_connection`].
+    fn add_connection_dyn(&mut self, addr: C) -> Result<ConnectionId, DynError>;
+    /// Remove a connection from the cable, see [`Cable::remove_connection`].
+    fn remove_connection_dyn(&mut self, id: ConnectionId) -> Option<C>;
+    /// Get a connection in the cable by its id, see [`Cable::get_mut`].
+    fn get_mut_dyn(&mut self, id: ConnectionId) -> Option<&mut C>;
+}
+
+impl<T: Cable + Connection> DynCable<T::Client> for T
+where
+    T::Error: Into<DynError>,
+    T::AddError: Into<DynError>,
+{
+    fn iter_child_dyn(&mut self) -> Box<dyn Iterator<Item = &mut T::Client> + '_> {
+        Box::new(self.iter_child())
+    }
+
+    fn iter_child_with_id_dyn(&mut self) -> Box<dyn Iterator<Item = (ConnectionId, &mut T::Client)> + '_> {
+        Box::new(self.iter_child_with_id())
+    }
+
+    fn add_connection_dyn(&mut self, addr: T::Client) -> Result<ConnectionId, DynError> {
+        self.add_connection(addr).map_err(Into::into)
+    }
+
+    fn remove_connection_dyn(&mut self, id: ConnectionId) -> Option<T::Client> {
+        self.remove_connection(id)
+    }
+
+    fn get_mut_dyn(&mut self, id: ConnectionId) -> Option<&mut T::Client> {
+        self.get_mut(id)
+    }
+}
+
+impl<C> Connection for dyn DynCable<C> + '_ {
+    type Error = DynError;
+
+    fn disconnect(&self) -> Result<(), Self::Error> {
+        self.disconnect_dyn()
+    }
+}
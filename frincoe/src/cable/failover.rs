@@ -0,0 +1,396 @@
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::fmt::{self, Display};
+use std::time::{Duration, Instant};
+
+use frincoe_rpc::Connection;
+
+use super::slot_map::{SlotEntriesMut, SlotIterMut, SlotMap};
+use super::{Bundle, Cable, ChildErrors, ConnectionId};
+
+
+
+/// Error of a call without any child available, e.g. all of them are backing off.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Unavailable;
+
+impl Display for Unavailable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "no healthy child to call")
+    }
+}
+
+impl std::error::Error for Unavailable {}
+
+impl From<Unavailable> for String {
+    fn from(error: Unavailable) -> Self {
+        error.to_string()
+    }
+}
+
+/// Results telling whether the call failed, used by [`dispatch_one`](frincoe_macros::dispatch_one) to fail over.
+pub trait Outcome {
+    /// Check if the call failed.
+    fn is_failure(&self) -> bool;
+    /// The failed result of a call without any child available.
+    fn unavailable() -> Self;
+}
+
+impl<T, E: From<Unavailable>> Outcome for Result<T, E> {
+    fn is_failure(&self) -> bool {
+        self.is_err()
+    }
+
+    fn unavailable() -> Self {
+        Err(Unavailable.into())
+    }
+}
+
+/// A bundle fails if any of its items fails.
+impl<T: Outcome, const N: usize> Outcome for Bundle<T, N> {
+    fn is_failure(&self) -> bool {
+        self.iter().any(Outcome::is_failure)
+    }
+
+    fn unavailable() -> Self {
+        Bundle::from_single(T::unavailable())
+    }
+}
+
+
+
+/// Health of a child, i.e. its consecutive failures and when to retry it.
+#[derive(Clone, Copy, Debug, Default)]
+struct Health {
+    failures: u32,
+    retry_at: Option<Instant>,
+}
+
+/**
+Cable calling its primary child, failing over to the next ones when the calls fail.
+
+The children are tried in the order they're added, skipping the unhealthy ones.
+A child is unhealthy after a failed call or disconnection,
+and it's not retried until a backoff has elapsed, which is doubled by each consecutive failure;
+a successful call makes it healthy again.
+
+Use the `failover` option of [`dispatch_one`](frincoe_macros::dispatch_one) to implement traits,
+so that the results of the calls feed the health of the children;
+the cable is also a [`Cable`], so [`dispatch_sub`](frincoe_macros::dispatch_sub) still broadcasts to all the children.
+
+```
+use frincoe::cable::{Bundle, Cable, FailoverCable};
+use frincoe_macros::{dispatch_one, inject_implement};
+# use frincoe_rpc::Connection;
+
+trait Store {
+    fn save(&mut self, key: &str) -> Bundle<Result<String, String>>;
+}
+
+struct Replica(&'static str, bool);
+impl Store for Replica {
+    fn save(&mut self, key: &str) -> Bundle<Result<String, String>> {
+        Bundle::from_single(if self.1 { Ok(format!("{} saved {}", self.0, key)) } else { Err(self.0.to_string()) })
+    }
+}
+# impl Connection for Replica {
+#     type Error = ();
+#     fn disconnect(&self) -> Result<(), ()> { Ok(()) }
+# }
+
+inject_implement! {
+    impl {
+        trait Store {
+            fn save(&mut self, key: &str) -> Bundle<Result<String, String>>;
+        }
+    } for FailoverCable<Replica> in dispatch_one(failover)
+}
+
+let mut cable = FailoverCable::new();
+let primary = cable.add_connection(Replica("primary", false)).unwrap();
+cable.add_connection(Replica("secondary", true)).unwrap();
+assert_eq!(cable.save("a"), [Ok("secondary saved a".to_string())]);
+// The primary is not retried until the backoff elapses
+assert!(!cable.is_available(primary));
+assert_eq!(cable.failures(primary), Some(1));
+
+// The last failure is returned when all the children fail
+let mut cable = FailoverCable::new();
+cable.add_connection(Replica("primary", false)).unwrap();
+cable.add_connection(Replica("secondary", false)).unwrap();
+assert_eq!(cable.save("b"), [Err("secondary".to_string())]);
+// And a failure is returned when all of them are backing off
+assert_eq!(cable.save("b"), [Err("no healthy child to call".to_string())]);
+```
+*/
+#[derive(Clone, Debug)]
+pub struct FailoverCable<T> {
+    child: SlotMap<T>,
+    /// Ids of the children in the order they're added.
+    ids: Vec<ConnectionId>,
+    health: HashMap<ConnectionId, Cell<Health>>,
+    backoff: Duration,
+    max_backoff: Duration,
+    /// The clock of the backoffs, replaced by the tests.
+    now: fn() -> Instant,
+}
+
+impl<T> Default for FailoverCable<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> FailoverCable<T> {
+    /// Create an empty FailoverCable, with a backoff from 1 second up to 1 minute.
+    pub fn new() -> Self {
+        Self::with_backoff(Duration::from_secs(1), Duration::from_secs(60))
+    }
+
+    /// Create an empty FailoverCable, with the backoff after the first failure and the maximum one.
+    pub fn with_backoff(backoff: Duration, max_backoff: Duration) -> Self {
+        assert!(
+            !backoff.is_zero(),
+            "the backoff should be positive, or a failed child would be retried at once"
+        );
+        Self {
+            child: SlotMap::new(),
+            ids: vec![],
+            health: HashMap::new(),
+            backoff,
+            max_backoff: max_backoff.max(backoff),
+            now: Instant::now,
+        }
+    }
+
+    /// Amount of the clients.
+    pub fn len(&self) -> usize {
+        self.child.len()
+    }
+
+    /// Check if there's no client.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Consecutive failures of a child, or `None` if it's absent.
+    pub fn failures(&self, id: ConnectionId) -> Option<u32> {
+        self.health.get(&id).map(|x| x.get().failures)
+    }
+
+    /// Check if a child is present and can be called, i.e. healthy or its backoff has elapsed.
+    pub fn is_available(&self, id: ConnectionId) -> bool {
+        self.available_at(id, (self.now)())
+    }
+
+    fn available_at(&self, id: ConnectionId, now: Instant) -> bool {
+        self.health
+            .get(&id)
+            .is_some_and(|x| x.get().retry_at.is_none_or(|retry_at| now >= retry_at))
+    }
+
+    /// Pick the first available child to call, or `None` if there's no such child.
+    pub fn pick_child(&mut self) -> Option<(ConnectionId, &mut T)> {
+        self.pick_untried(&[])
+    }
+
+    /**
+    Pick the first available child to call except the ones already tried, or `None` if there's no such child.
+
+    A failed child is available again once its backoff elapses,
+    so a call slower than the backoff would pick the failed children again and again;
+    the children tried by the call are skipped instead, so that each of them is tried at most once.
+
+    ```
+    use std::thread;
+    use std::time::Duration;
+
+    use frincoe::cable::{Cable, FailoverCable};
+    use frincoe_macros::{dispatch_one, inject_implement};
+
+    trait Store {
+        fn save(&mut self, key: &str) -> Result<(), String>;
+    }
+
+    /// A replica failing slower than the backoff.
+    struct Slow;
+    impl Store for Slow {
+        fn save(&mut self, _: &str) -> Result<(), String> {
+            thread::sleep(Duration::from_millis(5));
+            Err("timeout".to_string())
+        }
+    }
+
+    inject_implement! {
+        impl {
+            trait Store {
+                fn save(&mut self, key: &str) -> Result<(), String>;
+            }
+        } for FailoverCable<Slow> in dispatch_one(failover)
+    }
+
+    let millis = Duration::from_millis(1);
+    let mut cable = FailoverCable::with_backoff(millis, millis);
+    let a = cable.add_connection(Slow).unwrap();
+    let b = cable.add_connection(Slow).unwrap();
+    assert_eq!(cable.save("a"), Err("timeout".to_string()));
+    assert_eq!((cable.failures(a), cable.failures(b)), (Some(1), Some(1)));
+    ```
+    */
+    pub fn pick_untried(&mut self, tried: &[ConnectionId]) -> Option<(ConnectionId, &mut T)> {
+        let now = (self.now)();
+        let id = *self
+            .ids
+            .iter()
+            .find(|id| !tried.contains(id) && self.available_at(**id, now))?;
+        self.child.get_mut(id).map(|x| (id, x))
+    }
+
+    /// Record whether a call to a child succeeds, updating its health.
+    pub fn report_call(&mut self, id: ConnectionId, ok: bool) {
+        self.report(id, ok);
+    }
+
+    fn report(&self, id: ConnectionId, ok: bool) {
+        let health = match self.health.get(&id) {
+            Some(v) => v,
+            None => return,
+        };
+        if ok {
+            health.set(Health::default());
+            return;
+        }
+        let failures = health.get().failures.saturating_add(1);
+        let backoff = self
+            .backoff
+            .checked_mul(1 << (failures - 1).min(31))
+            .map_or(self.max_backoff, |x| x.min(self.max_backoff));
+        health.set(Health {
+            failures,
+            retry_at: Some((self.now)() + backoff),
+        });
+    }
+}
+
+impl<T: Connection> Connection for FailoverCable<T> {
+    type Error = ChildErrors<T::Error>;
+
+    /// Disconnect all the children, the failed ones are recorded as unhealthy.
+    fn disconnect(&self) -> Result<(), Self::Error> {
+        self.child
+            .iter()
+            .filter_map(|(id, x)| x.disconnect().err().map(|e| (id, e)))
+            .inspect(|(id, _)| self.report(*id, false))
+            .collect::<ChildErrors<_>>()
+            .into_result()
+    }
+}
+
+impl<T> Cable for FailoverCable<T> {
+    type AddError = Infallible;
+    type ChildIdIter<'a>
+        = SlotEntriesMut<'a, T>
+    where
+        T: 'a;
+    type ChildIter<'a>
+        = SlotIterMut<'a, T>
+    where
+        T: 'a;
+    type Client = T;
+
+    fn iter_child(&mut self) -> Self::ChildIter<'_> {
+        self.child.iter_mut()
+    }
+
+    fn iter_child_with_id(&mut self) -> Self::ChildIdIter<'_> {
+        self.child.entries_mut()
+    }
+
+    fn add_connection(&mut self, addr: Self::Client) -> Result<ConnectionId, Self::AddError> {
+        let id = self.child.insert(addr);
+        self.ids.push(id);
+        self.health.insert(id, Cell::default());
+        Ok(id)
+    }
+
+    fn remove_connection(&mut self, id: ConnectionId) -> Option<Self::Client> {
+        let client = self.child.remove(id)?;
+        self.ids.retain(|x| *x != id);
+        self.health.remove(&id);
+        Some(client)
+    }
+
+    fn get_mut(&mut self, id: ConnectionId) -> Option<&mut Self::Client> {
+        self.child.get_mut(id)
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::time::{Duration, Instant};
+
+    use frincoe_rpc::Connection;
+
+    use super::FailoverCable;
+    use crate::cable::Cable;
+
+    /// A child failing to disconnect if `fail` is set.
+    struct Child {
+        fail: bool,
+    }
+
+    impl Connection for Child {
+        type Error = ();
+
+        fn disconnect(&self) -> Result<(), Self::Error> {
+            if self.fail {
+                Err(())
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    thread_local! {
+        static START: Instant = Instant::now();
+        static ELAPSED: Cell<Duration> = Cell::default();
+    }
+
+    /// A clock advanced by the tests.
+    fn now() -> Instant {
+        START.with(|x| *x) + ELAPSED.with(Cell::get)
+    }
+
+    #[test]
+    fn backoff() {
+        let mut cable = FailoverCable::with_backoff(Duration::from_millis(200), Duration::from_millis(300));
+        cable.now = now;
+        let a = cable.add_connection(Child { fail: true }).unwrap();
+        let b = cable.add_connection(Child { fail: false }).unwrap();
+        assert_eq!(cable.pick_child().map(|(id, _)| id), Some(a));
+        // Disconnection errors make the child unhealthy
+        assert!(cable.disconnect().is_err());
+        assert_eq!(cable.failures(a), Some(1));
+        assert_eq!(cable.pick_child().map(|(id, _)| id), Some(b));
+        cable.report_call(b, false);
+        assert!(cable.pick_child().is_none());
+        // Retried after the backoff, and healthy after a success
+        ELAPSED.with(|x| x.set(Duration::from_millis(199)));
+        assert!(cable.pick_child().is_none());
+        ELAPSED.with(|x| x.set(Duration::from_millis(200)));
+        assert_eq!(cable.pick_child().map(|(id, _)| id), Some(a));
+        // Unless it's already tried by the call
+        assert_eq!(cable.pick_untried(&[a]).map(|(id, _)| id), Some(b));
+        assert!(cable.pick_untried(&[a, b]).is_none());
+        cable.report_call(a, false);
+        assert_eq!(cable.failures(a), Some(2));
+        cable.report_call(b, true);
+        assert_eq!(cable.failures(b), Some(0));
+        assert!(cable.is_available(b));
+        assert!(!cable.is_available(a));
+    }
+}
@@ -0,0 +1,529 @@
+use std::any::Any;
+use std::convert::Infallible;
+use std::fmt::{self, Debug};
+use std::future::{poll_fn, Future};
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::pin;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::task::Poll;
+use std::thread;
+use std::time::Duration;
+
+use frincoe_rpc::{CallContext, Connection};
+
+use super::slot_map::{SlotEntriesMut, SlotIterMut, SlotMap};
+use super::{Cable, ChildErrors, ConnectionId};
+
+
+
+/// Counters of the calls of a [`HedgedCable`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct HedgeStats {
+    /// Amount of the calls made to a child.
+    pub calls: u64,
+    /// Amount of the calls sent to a second child because the first one didn't answer in time.
+    pub hedged: u64,
+    /// Amount of the hedged calls answered by the second child first.
+    pub hedge_won: u64,
+}
+
+/**
+Token of a threaded hedged call, telling it whether the other call has already answered.
+
+Threads can't be stopped from outside, so a losing call runs to its end and only its result is dropped;
+long calls can check [`CancelToken::is_cancelled`] from time to time to give up early instead.
+*/
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    /// Check if the other call has answered, so that the result of this one will be dropped.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Acquire)
+    }
+
+    fn cancel(&self) {
+        self.0.store(true, Ordering::Release);
+    }
+}
+
+
+
+/// A call run by the [`Workers`].
+type Job = Box<dyn FnOnce() + Send>;
+
+/// Threads running the threaded hedged calls, kept for the next calls until the cable is dropped.
+struct Workers {
+    sender: mpsc::Sender<Job>,
+    receiver: Arc<Mutex<mpsc::Receiver<Job>>>,
+    /// Amount of the threads waiting for a job, which no job is sent to yet.
+    idle: Arc<AtomicUsize>,
+}
+
+impl Workers {
+    fn new() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        Self {
+            sender,
+            receiver: Arc::new(Mutex::new(receiver)),
+            idle: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Run the job on a waiting thread, or on a new one if all of them are busy.
+    fn run(&self, job: Job) {
+        if self
+            .idle
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| n.checked_sub(1))
+            .is_ok()
+        {
+            // The receiver is kept by `self`, so the job is always received
+            let _ = self.sender.send(job);
+            return;
+        }
+        let (receiver, idle) = (self.receiver.clone(), self.idle.clone());
+        thread::spawn(move || {
+            job();
+            loop {
+                idle.fetch_add(1, Ordering::AcqRel);
+                // Fails when the cable is dropped
+                let job = receiver.lock().unwrap_or_else(|e| e.into_inner()).recv();
+                match job {
+                    Ok(job) => job(),
+                    Err(_) => break,
+                }
+            }
+        });
+    }
+}
+
+impl Debug for Workers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Workers").field("idle", &self.idle).finish()
+    }
+}
+
+
+
+/**
+Cable calling one of its children, and a second one if the first doesn't answer within a delay.
+
+The first child is picked in turn, and the second one is the child added after it;
+the result of whichever answers first is returned, and the other one is discarded.
+[`HedgedCable::hedge`] calls the children on worker threads kept by the cable,
+while [`HedgedCable::hedge_async`] polls them as futures without depending on any runtime.
+A slow first child should be a rare event, so the delay is usually set around a high percentile of the latency,
+and [`HedgedCable::stats`] tells how often the hedging fires.
+
+Threads can't be stopped, so a losing threaded call runs to its end in the background and only its result is dropped,
+unless it gives up on its [`CancelToken`]; a losing async call is cancelled by dropping its future.
+Hedging doubles the load of the slow calls, so it fits idempotent calls like reads.
+
+Use the `hedge` option of [`dispatch_one`](frincoe_macros::dispatch_one) to implement traits,
+the children are shared with the threads, so the methods take `&self`;
+the cable is also a [`Cable`], so [`dispatch_sub`](frincoe_macros::dispatch_sub) still broadcasts to all the children.
+
+```
+use std::thread;
+use std::time::Duration;
+
+use frincoe::cable::{Cable, HedgedCable};
+use frincoe_macros::{dispatch_one, inject_implement};
+# use frincoe_rpc::Connection;
+
+trait Read {
+    fn read(&self, key: String) -> Option<String>;
+}
+
+struct Replica(&'static str, Duration);
+impl Read for Replica {
+    fn read(&self, key: String) -> Option<String> {
+        thread::sleep(self.1);
+        Some(format!("{} from {}", key, self.0))
+    }
+}
+# impl Connection for Replica {
+#     type Error = ();
+#     fn disconnect(&self) -> Result<(), ()> { Ok(()) }
+# }
+
+inject_implement! {
+    impl {
+        trait Read {
+            fn read(&self, key: String) -> Option<String>;
+        }
+    } for HedgedCable<Replica> in dispatch_one(hedge)
+}
+
+let mut cable = HedgedCable::with_delay(Duration::from_millis(10));
+cable.add_connection(Replica("slow", Duration::from_secs(1)).into()).unwrap();
+cable.add_connection(Replica("fast", Duration::ZERO).into()).unwrap();
+assert_eq!(cable.read("a".to_string()), Some("a from fast".to_string()));
+assert_eq!(cable.stats().hedged, 1);
+assert_eq!(cable.stats().hedge_won, 1);
+```
+*/
+#[derive(Debug)]
+pub struct HedgedCable<T> {
+    child: SlotMap<Arc<T>>,
+    /// Ids of the children in the order they're added.
+    ids: Vec<ConnectionId>,
+    delay: Duration,
+    next: AtomicUsize,
+    workers: Workers,
+    calls: AtomicU64,
+    hedged: AtomicU64,
+    hedge_won: AtomicU64,
+}
+
+impl<T> Default for HedgedCable<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> HedgedCable<T> {
+    /// Create an empty HedgedCable, hedging after 10 milliseconds.
+    pub fn new() -> Self {
+        Self::with_delay(Duration::from_millis(10))
+    }
+
+    /// Create an empty HedgedCable, hedging after the delay.
+    pub fn with_delay(delay: Duration) -> Self {
+        Self {
+            child: SlotMap::new(),
+            ids: vec![],
+            delay,
+            next: AtomicUsize::new(0),
+            workers: Workers::new(),
+            calls: AtomicU64::new(0),
+            hedged: AtomicU64::new(0),
+            hedge_won: AtomicU64::new(0),
+        }
+    }
+
+    /// Amount of the clients.
+    pub fn len(&self) -> usize {
+        self.child.len()
+    }
+
+    /// Check if there's no client.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The delay before calling the second child.
+    pub fn delay(&self) -> Duration {
+        self.delay
+    }
+
+    /// Counters of the calls so far.
+    pub fn stats(&self) -> HedgeStats {
+        HedgeStats {
+            calls: self.calls.load(Ordering::Relaxed),
+            hedged: self.hedged.load(Ordering::Relaxed),
+            hedge_won: self.hedge_won.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Pick the child to call first and the one to hedge with, counting a call.
+    fn pick_pair(&self) -> Option<(Arc<T>, Option<Arc<T>>)> {
+        if self.ids.is_empty() {
+            return None;
+        }
+        self.calls.fetch_add(1, Ordering::Relaxed);
+        let index = self.next.fetch_add(1, Ordering::Relaxed) % self.ids.len();
+        let first = self.child.get(self.ids[index])?.clone();
+        let second = match self.ids.len() {
+            1 => None,
+            len => self.child.get(self.ids[(index + 1) % len]).cloned(),
+        };
+        Some((first, second))
+    }
+
+    /// Record which child of a hedged call answers first.
+    fn finish_hedge(&self, second_won: bool) {
+        if second_won {
+            self.hedge_won.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /**
+    Call a child with the closure on a worker thread, and a second child if the first doesn't answer within the delay.
+
+    Returns the first answer, or `None` if there's no child;
+    a first call panicking before the delay is hedged at once, and if both of the calls panic, the panic is resumed.
+    The closure is given a [`CancelToken`], cancelled when the other call answers,
+    and the [`CallContext`] of the caller is carried to the workers.
+    The workers are reused by the next calls once they're done, so a thread is only started when all of them are busy.
+    With a single child, it's called on the current thread.
+    */
+    pub fn hedge<R, F>(&self, f: F) -> Option<R>
+    where
+        T: Send + Sync + 'static,
+        R: Send + 'static,
+        F: Fn(&T, &CancelToken) -> R + Send + Sync + 'static,
+    {
+        let (first, second) = self.pick_pair()?;
+        let cancel = CancelToken::default();
+        let second = match second {
+            Some(v) => v,
+            None => return Some(f(&first, &cancel)),
+        };
+        let f = Arc::new(f);
+        let context = CallContext::current();
+        let (sender, receiver) = mpsc::channel::<(bool, Result<R, Box<dyn Any + Send>>)>();
+        let spawn = |child: Arc<T>, hedging: bool| {
+            let (f, context, cancel) = (f.clone(), context.clone(), cancel.clone());
+            let sender = sender.clone();
+            self.workers.run(Box::new(move || {
+                // A panicking call answers nothing, so that the other call is waited
+                let res = panic::catch_unwind(AssertUnwindSafe(|| context.scope(|| f(&child, &cancel))));
+                let _ = sender.send((hedging, res));
+            }));
+        };
+        spawn(first, false);
+        let mut running = 1;
+        let mut panicked = None;
+        if let Ok((_, res)) = receiver.recv_timeout(self.delay) {
+            match res {
+                Ok(res) => return Some(res),
+                Err(e) => panicked = Some(e),
+            }
+            running = 0;
+        }
+        self.hedged.fetch_add(1, Ordering::Relaxed);
+        spawn(second, true);
+        for _ in 0..=running {
+            match receiver.recv() {
+                Ok((hedging, Ok(res))) => {
+                    cancel.cancel();
+                    self.finish_hedge(hedging);
+                    return Some(res);
+                }
+                Ok((_, Err(e))) => panicked = Some(e),
+                Err(_) => break,
+            }
+        }
+        panic::resume_unwind(panicked.expect("a hedged call neither answers nor panics"))
+    }
+
+    /**
+    Call a child with the closure, and a second child if the first doesn't answer within the delay, asynchronously.
+
+    The delay is waited with `sleep`, e.g. `tokio::time::sleep`, so that any runtime can be used.
+    Returns the first answer, or `None` if there's no child; the other call is cancelled by dropping its future.
+    */
+    pub async fn hedge_async<R, F, S, D>(&self, mut call: impl FnMut(Arc<T>) -> F, sleep: S) -> Option<R>
+    where
+        F: Future<Output = R>,
+        S: FnOnce(Duration) -> D,
+        D: Future<Output = ()>,
+    {
+        let (first, second) = self.pick_pair()?;
+        let first = call(first);
+        let mut second = match second {
+            Some(v) => Some(v),
+            None => return Some(first.await),
+        };
+        let mut first = pin!(first);
+        let mut delay = pin!(sleep(self.delay));
+        let mut hedge = pin!(None);
+        let (hedging, res) = poll_fn(|cx| {
+            if let Poll::Ready(res) = first.as_mut().poll(cx) {
+                return Poll::Ready((false, res));
+            }
+            if let Some(child) = second.take() {
+                if delay.as_mut().poll(cx).is_pending() {
+                    second = Some(child);
+                    return Poll::Pending;
+                }
+                self.hedged.fetch_add(1, Ordering::Relaxed);
+                hedge.set(Some(call(child)));
+            }
+            match hedge.as_mut().as_pin_mut() {
+                Some(v) => v.poll(cx).map(|res| (true, res)),
+                None => Poll::Pending,
+            }
+        })
+        .await;
+        self.finish_hedge(hedging);
+        Some(res)
+    }
+}
+
+impl<T: Connection> Connection for HedgedCable<T> {
+    type Error = ChildErrors<T::Error>;
+
+    fn disconnect(&self) -> Result<(), Self::Error> {
+        self.child
+            .iter()
+            .filter_map(|(id, x)| x.disconnect().err().map(|e| (id, e)))
+            .collect::<ChildErrors<_>>()
+            .into_result()
+    }
+}
+
+impl<T> Cable for HedgedCable<T> {
+    type AddError = Infallible;
+    type ChildIdIter<'a>
+        = SlotEntriesMut<'a, Arc<T>>
+    where
+        T: 'a;
+    type ChildIter<'a>
+        = SlotIterMut<'a, Arc<T>>
+    where
+        T: 'a;
+    type Client = Arc<T>;
+
+    fn iter_child(&mut self) -> Self::ChildIter<'_> {
+        self.child.iter_mut()
+    }
+
+    fn iter_child_with_id(&mut self) -> Self::ChildIdIter<'_> {
+        self.child.entries_mut()
+    }
+
+    fn add_connection(&mut self, addr: Self::Client) -> Result<ConnectionId, Self::AddError> {
+        let id = self.child.insert(addr);
+        self.ids.push(id);
+        Ok(id)
+    }
+
+    fn remove_connection(&mut self, id: ConnectionId) -> Option<Self::Client> {
+        let client = self.child.remove(id)?;
+        self.ids.retain(|x| *x != id);
+        Some(client)
+    }
+
+    fn get_mut(&mut self, id: ConnectionId) -> Option<&mut Self::Client> {
+        self.child.get_mut(id)
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::future::Future;
+    use std::panic::{self, AssertUnwindSafe};
+    use std::pin::{pin, Pin};
+    use std::sync::{mpsc, Arc};
+    use std::task::{Context, Poll, Waker};
+    use std::thread;
+    use std::time::Duration;
+
+    use frincoe_rpc::Connection;
+
+    use super::{HedgeStats, HedgedCable};
+    use crate::cable::Cable;
+
+    /// A child answering after being polled the given times.
+    struct Child(u32);
+
+    impl Connection for Child {
+        type Error = ();
+
+        fn disconnect(&self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    /// A future ready after being polled the given times.
+    struct Ticks(u32);
+
+    impl Future for Ticks {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<()> {
+            match self.0 {
+                0 => Poll::Ready(()),
+                _ => {
+                    self.0 -= 1;
+                    Poll::Pending
+                }
+            }
+        }
+    }
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = pin!(future);
+        let mut cx = Context::from_waker(Waker::noop());
+        loop {
+            if let Poll::Ready(v) = future.as_mut().poll(&mut cx) {
+                return v;
+            }
+        }
+    }
+
+    fn call(child: Arc<Child>) -> impl Future<Output = u32> {
+        let ticks = child.0;
+        async move {
+            Ticks(ticks).await;
+            ticks
+        }
+    }
+
+    #[test]
+    fn hedge_async() {
+        let mut cable = HedgedCable::new();
+        assert_eq!(block_on(cable.hedge_async(call, |_| Ticks(2))), None);
+        cable.add_connection(Arc::new(Child(1))).unwrap();
+        cable.add_connection(Arc::new(Child(10))).unwrap();
+        cable.add_connection(Arc::new(Child(3))).unwrap();
+        cable.add_connection(Arc::new(Child(9))).unwrap();
+        // Answered before the delay
+        assert_eq!(block_on(cable.hedge_async(call, |_| Ticks(2))), Some(1));
+        // Hedged, and the second child wins
+        assert_eq!(block_on(cable.hedge_async(call, |_| Ticks(2))), Some(3));
+        // Hedged, but the first child wins
+        assert_eq!(block_on(cable.hedge_async(call, |_| Ticks(2))), Some(3));
+        assert_eq!(
+            cable.stats(),
+            HedgeStats {
+                calls: 3,
+                hedged: 2,
+                hedge_won: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn hedge_panicked() {
+        let mut cable = HedgedCable::with_delay(Duration::from_secs(60));
+        cable.add_connection(Arc::new(Child(0))).unwrap();
+        cable.add_connection(Arc::new(Child(1))).unwrap();
+        // The panic of the first call hedges at once
+        assert_eq!(
+            cable.hedge(|x, _| if x.0 == 0 { panic!("first child fails") } else { x.0 }),
+            Some(1)
+        );
+        let both = panic::catch_unwind(AssertUnwindSafe(|| {
+            cable.hedge(|_, _| -> u32 { panic!("both children fail") })
+        }));
+        assert_eq!(both.unwrap_err().downcast_ref(), Some(&"both children fail"));
+        assert_eq!(cable.stats().hedged, 2);
+    }
+
+    #[test]
+    fn hedge_cancel() {
+        let mut cable = HedgedCable::with_delay(Duration::from_millis(10));
+        cable.add_connection(Arc::new(Child(0))).unwrap();
+        cable.add_connection(Arc::new(Child(1))).unwrap();
+        let (sender, receiver) = mpsc::channel();
+        let res = cable.hedge(move |x, cancel| {
+            if x.0 == 0 {
+                // The first child waits until the second one answers
+                while !cancel.is_cancelled() {
+                    thread::sleep(Duration::from_millis(1));
+                }
+                sender.send(()).unwrap();
+            }
+            x.0
+        });
+        assert_eq!(res, Some(1));
+        assert_eq!(receiver.recv_timeout(Duration::from_secs(10)), Ok(()));
+        assert_eq!(cable.stats().hedge_won, 1);
+    }
+}
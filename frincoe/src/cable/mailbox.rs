@@ -0,0 +1,567 @@
+use std::collections::VecDeque;
+use std::fmt::{self, Display};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{mpsc, Arc, Condvar, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+
+use frincoe_rpc::{CallContext, Connection};
+
+use super::slot_map::SlotMap;
+use super::{ChildErrors, ConnectionId};
+
+
+
+/// What a [`MailboxCable`] does when a message is posted to a full mailbox.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Overflow {
+    /// Wait until the mailbox has room.
+    #[default]
+    Block,
+    /// Drop the oldest message in the mailbox to make room.
+    DropOldest,
+    /// Drop the message posted.
+    DropNewest,
+    /// Drop the message posted, and return a [`MailboxFull`] error.
+    Error,
+}
+
+/// Error of posting to a full mailbox with the [`Overflow::Error`] behaviour.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MailboxFull;
+
+impl Display for MailboxFull {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the mailbox is full")
+    }
+}
+
+impl std::error::Error for MailboxFull {}
+
+
+
+type Job<T> = Box<dyn FnOnce(&mut T) + Send>;
+
+struct State<T> {
+    jobs: VecDeque<Job<T>>,
+    /// Whether the worker is running a job.
+    busy: bool,
+    closed: bool,
+}
+
+/// Queue of a mailbox, shared by the cable and the worker.
+struct Queue<T> {
+    state: Mutex<State<T>>,
+    changed: Condvar,
+}
+
+impl<T> Queue<T> {
+    fn new() -> Self {
+        Self {
+            state: Mutex::new(State {
+                jobs: VecDeque::new(),
+                busy: false,
+                closed: false,
+            }),
+            changed: Condvar::new(),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State<T>> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn wait<'a>(&self, guard: MutexGuard<'a, State<T>>) -> MutexGuard<'a, State<T>> {
+        self.changed.wait(guard).unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Push a job, returning whether a job is dropped.
+    fn push(&self, job: Job<T>, capacity: usize, overflow: Overflow) -> Result<bool, MailboxFull> {
+        let mut state = self.lock();
+        let mut dropped = false;
+        if state.jobs.len() >= capacity {
+            match overflow {
+                Overflow::Block => {
+                    while state.jobs.len() >= capacity {
+                        state = self.wait(state);
+                    }
+                }
+                Overflow::DropOldest => {
+                    state.jobs.pop_front();
+                    dropped = true;
+                }
+                Overflow::DropNewest => return Ok(true),
+                Overflow::Error => return Err(MailboxFull),
+            }
+        }
+        state.jobs.push_back(job);
+        self.changed.notify_all();
+        Ok(dropped)
+    }
+
+    /// Take the next job for the worker, or `None` if the mailbox is closed and drained.
+    fn next(&self) -> Option<Job<T>> {
+        let mut state = self.lock();
+        loop {
+            if let Some(job) = state.jobs.pop_front() {
+                state.busy = true;
+                self.changed.notify_all();
+                return Some(job);
+            }
+            if state.closed {
+                return None;
+            }
+            state = self.wait(state);
+        }
+    }
+
+    fn done(&self) {
+        self.lock().busy = false;
+        self.changed.notify_all();
+    }
+
+    fn drain(&self) {
+        let mut state = self.lock();
+        while !state.jobs.is_empty() || state.busy {
+            state = self.wait(state);
+        }
+    }
+
+    fn close(&self) {
+        self.lock().closed = true;
+        self.changed.notify_all();
+    }
+}
+
+/// A child owned by its worker thread, and the queue of it.
+struct Mailbox<T> {
+    queue: Arc<Queue<T>>,
+    worker: Option<JoinHandle<T>>,
+}
+
+impl<T: Send + 'static> Mailbox<T> {
+    fn spawn(mut child: T) -> Self {
+        let queue = Arc::new(Queue::new());
+        let worker = {
+            let queue = queue.clone();
+            thread::spawn(move || {
+                while let Some(job) = queue.next() {
+                    // A panicking call loses only its own message
+                    let _ = panic::catch_unwind(AssertUnwindSafe(|| job(&mut child)));
+                    queue.done();
+                }
+                child
+            })
+        };
+        Self {
+            queue,
+            worker: Some(worker),
+        }
+    }
+}
+
+impl<T> Mailbox<T> {
+    /// Close the mailbox and wait for the worker to drain it, returning the child.
+    fn join(&mut self) -> Option<T> {
+        self.queue.close();
+        self.worker.take()?.join().ok()
+    }
+}
+
+impl<T> Drop for Mailbox<T> {
+    fn drop(&mut self) {
+        self.join();
+    }
+}
+
+
+
+/**
+Cable running each of its children on a worker thread, decoupling the callers from the children.
+
+Each child owns a bounded mailbox of messages, i.e. the calls to it, run by its worker in order.
+[`MailboxCable::post`] enqueues a call to every child and returns at once,
+and [`MailboxCable::ask`] enqueues a call and waits for the results;
+when a mailbox is full, the [`Overflow`] behaviour decides what happens to the new message.
+[`MailboxCable::flush`] waits until every mailbox is drained, and dropping the cable drains them too.
+
+Use the `mailbox` option of [`dispatch_sub`](frincoe_macros::dispatch_sub) to implement traits,
+so that methods without results are posted, keeping the errors for [`MailboxCable::take_rejected`],
+and the ones with results are asked.
+Since the children are moved to the workers, the cable isn't a [`Cable`](super::Cable),
+and it provides `add_connection` and `remove_connection` by itself.
+The [`CallContext`] of the caller is carried to the workers, so the children can read it as usual.
+
+```
+use std::sync::mpsc::{channel, Sender};
+
+use frincoe::cable::{Bundle, MailboxCable, Overflow};
+use frincoe_macros::{dispatch_sub, inject_implement};
+# use frincoe_rpc::Connection;
+
+trait Events {
+    fn clicked(&mut self, button: String);
+    fn count(&mut self) -> Bundle<usize>;
+}
+
+struct Listener(Sender<String>, usize);
+impl Events for Listener {
+    fn clicked(&mut self, button: String) {
+        self.1 += 1;
+        self.0.send(button).unwrap();
+    }
+    fn count(&mut self) -> Bundle<usize> {
+        Bundle::from_single(self.1)
+    }
+}
+# impl Connection for Listener {
+#     type Error = ();
+#     fn disconnect(&self) -> Result<(), ()> { Ok(()) }
+# }
+
+inject_implement! {
+    impl {
+        trait Events {
+            fn clicked(&mut self, button: String);
+            fn count(&mut self) -> Bundle<usize>;
+        }
+    } for MailboxCable<Listener> in dispatch_sub(mailbox)
+}
+
+let (sender, receiver) = channel();
+let mut cable = MailboxCable::with_capacity(16, Overflow::Block);
+cable.add_connection(Listener(sender.clone(), 0));
+cable.add_connection(Listener(sender, 0));
+cable.clicked("ok".to_string());
+cable.flush();
+assert_eq!(receiver.try_iter().collect::<Vec<_>>(), ["ok", "ok"]);
+assert_eq!(cable.count(), [1, 1]);
+```
+*/
+pub struct MailboxCable<T> {
+    child: SlotMap<Mailbox<T>>,
+    capacity: usize,
+    overflow: Overflow,
+    dropped: AtomicU64,
+    /// The last errors of a post without a caller to return them to, not taken yet.
+    rejected: Mutex<Option<ChildErrors<MailboxFull>>>,
+}
+
+impl<T> Default for MailboxCable<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> MailboxCable<T> {
+    /// Create an empty MailboxCable, with mailboxes of 1024 messages blocking when full.
+    pub fn new() -> Self {
+        Self::with_capacity(1024, Overflow::Block)
+    }
+
+    /// Create an empty MailboxCable, with the capacity of each mailbox and the overflow behaviour.
+    pub fn with_capacity(capacity: usize, overflow: Overflow) -> Self {
+        assert!(capacity > 0, "a mailbox should hold at least one message");
+        Self {
+            child: SlotMap::new(),
+            capacity,
+            overflow,
+            dropped: AtomicU64::new(0),
+            rejected: Mutex::new(None),
+        }
+    }
+
+    /// Amount of the clients.
+    pub fn len(&self) -> usize {
+        self.child.len()
+    }
+
+    /// Check if there's no client.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The capacity of each mailbox.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// The overflow behaviour.
+    pub fn overflow(&self) -> Overflow {
+        self.overflow
+    }
+
+    /// Amount of the messages dropped or rejected because of full mailboxes so far.
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /**
+    Take the last errors of the posts by methods without results, if there's any since the last time.
+
+    With the `mailbox` option of [`dispatch_sub`](frincoe_macros::dispatch_sub),
+    methods without results can't return the errors of [`Overflow::Error`], so they're kept here instead.
+
+    ```
+    use std::sync::{Arc, Barrier};
+
+    use frincoe::cable::{MailboxCable, Overflow};
+    use frincoe_macros::{dispatch_sub, inject_implement};
+
+    trait Work {
+        fn run(&mut self, barrier: Arc<Barrier>);
+    }
+
+    struct Worker;
+    impl Work for Worker {
+        fn run(&mut self, barrier: Arc<Barrier>) {
+            barrier.wait();
+        }
+    }
+
+    inject_implement! {
+        impl {
+            trait Work {
+                fn run(&mut self, barrier: Arc<Barrier>);
+            }
+        } for MailboxCable<Worker> in dispatch_sub(mailbox)
+    }
+
+    let mut cable = MailboxCable::with_capacity(1, Overflow::Error);
+    let id = cable.add_connection(Worker);
+    let barrier = Arc::new(Barrier::new(2));
+    // The worker waits in the first call, and the second one fills the mailbox
+    cable.run(barrier.clone());
+    while cable.pending(id) != Some(0) {
+        std::thread::yield_now();
+    }
+    cable.run(barrier.clone());
+    assert!(cable.take_rejected().is_none());
+    cable.run(barrier.clone());
+    let rejected = cable.take_rejected().unwrap();
+    assert_eq!(rejected.iter().map(|(id, _)| *id).collect::<Vec<_>>(), [id]);
+    assert_eq!(cable.dropped(), 1);
+    barrier.wait();
+    barrier.wait();
+    ```
+    */
+    pub fn take_rejected(&self) -> Option<ChildErrors<MailboxFull>> {
+        self.rejected.lock().unwrap_or_else(|e| e.into_inner()).take()
+    }
+
+    /// Keep the errors of a post without a caller to return them to.
+    pub fn report_rejected(&self, errors: ChildErrors<MailboxFull>) {
+        *self.rejected.lock().unwrap_or_else(|e| e.into_inner()) = Some(errors);
+    }
+
+    /// Amount of the messages waiting in the mailbox of a child, or `None` if it's absent.
+    pub fn pending(&self, id: ConnectionId) -> Option<usize> {
+        self.child.get(id).map(|x| x.queue.lock().jobs.len())
+    }
+
+    /// Wait until every mailbox is drained, i.e. all the messages posted so far are handled.
+    pub fn flush(&self) {
+        for (_, mailbox) in self.child.iter() {
+            mailbox.queue.drain();
+        }
+    }
+
+    /// Remove a child, waiting for its mailbox to be drained, and returning it if it's present.
+    ///
+    /// The connection is not disconnected, which is left to the caller.
+    pub fn remove_connection(&mut self, id: ConnectionId) -> Option<T> {
+        self.child.remove(id)?.join()
+    }
+}
+
+impl<T: Send + 'static> MailboxCable<T> {
+    /// Add a child, moving it to a new worker thread.
+    pub fn add_connection(&mut self, client: T) -> ConnectionId {
+        self.child.insert(Mailbox::spawn(client))
+    }
+
+    /**
+    Enqueue a call to every child, returning at once.
+
+    When a mailbox is full, the message is handled by the overflow behaviour,
+    and only [`Overflow::Error`] reports the children whose mailboxes are full;
+    the messages dropped are counted by [`MailboxCable::dropped`] in any case.
+    */
+    pub fn post(&self, f: impl Fn(&mut T) + Send + Sync + 'static) -> Result<(), ChildErrors<MailboxFull>> {
+        let f = Arc::new(f);
+        let context = CallContext::current();
+        let mut errors = ChildErrors::new();
+        for (id, mailbox) in self.child.iter() {
+            let (f, context) = (f.clone(), context.clone());
+            let job = Box::new(move |it: &mut T| context.scope(|| f(it)));
+            match mailbox.queue.push(job, self.capacity, self.overflow) {
+                Ok(false) => {}
+                Ok(true) => {
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                }
+                Err(e) => {
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                    errors.push(id, e);
+                }
+            }
+        }
+        errors.into_result()
+    }
+
+    /**
+    Enqueue a call to every child, and wait for the results.
+
+    The calls are never dropped by the overflow behaviour, but wait until the mailboxes have room;
+    the results of the calls panicking are missing.
+    */
+    pub fn ask<R: Send + 'static>(&self, f: impl Fn(&mut T) -> R + Send + Sync + 'static) -> Vec<R> {
+        let f = Arc::new(f);
+        let context = CallContext::current();
+        let (sender, receiver) = mpsc::channel();
+        for (index, (_, mailbox)) in self.child.iter().enumerate() {
+            let (f, context, sender) = (f.clone(), context.clone(), sender.clone());
+            let job = Box::new(move |it: &mut T| {
+                let _ = sender.send((index, context.scope(|| f(it))));
+            });
+            let _ = mailbox.queue.push(job, self.capacity, Overflow::Block);
+        }
+        drop(sender);
+        let mut res = receiver.iter().collect::<Vec<_>>();
+        res.sort_by_key(|(index, _)| *index);
+        res.into_iter().map(|(_, x)| x).collect()
+    }
+}
+
+impl<T> Drop for MailboxCable<T> {
+    /// Close all the mailboxes before waiting for any of them, so that they're drained in parallel.
+    fn drop(&mut self) {
+        for (_, mailbox) in self.child.iter() {
+            mailbox.queue.close();
+        }
+    }
+}
+
+impl<T> fmt::Debug for MailboxCable<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MailboxCable")
+            .field("len", &self.len())
+            .field("capacity", &self.capacity)
+            .field("overflow", &self.overflow)
+            .field("dropped", &self.dropped())
+            .finish()
+    }
+}
+
+impl<T> Connection for MailboxCable<T>
+where
+    T: Connection + Send + 'static,
+    T::Error: Send + 'static,
+{
+    type Error = ChildErrors<T::Error>;
+
+    /// Disconnect all the children on their workers, after the messages posted before.
+    fn disconnect(&self) -> Result<(), Self::Error> {
+        let ids = self.child.iter().map(|(id, _)| id).collect::<Vec<_>>();
+        let (sender, receiver) = mpsc::channel();
+        for (id, mailbox) in self.child.iter() {
+            let sender = sender.clone();
+            let job = Box::new(move |it: &mut T| {
+                let _ = sender.send((id, it.disconnect()));
+            });
+            let _ = mailbox.queue.push(job, self.capacity, Overflow::Block);
+        }
+        drop(sender);
+        let mut errors = receiver
+            .iter()
+            .filter_map(|(id, x)| x.err().map(|e| (id, e)))
+            .collect::<Vec<_>>();
+        errors.sort_by_key(|(id, _)| ids.iter().position(|x| x == id));
+        errors.into_iter().collect::<ChildErrors<_>>().into_result()
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Barrier};
+
+    use frincoe_rpc::{CallContext, Headers};
+
+    use super::{MailboxCable, MailboxFull, Overflow};
+
+    /// Wait at the barrier for the first message, so that the later ones stay in the mailbox.
+    fn blocked(overflow: Overflow) -> (MailboxCable<Vec<u32>>, Arc<Barrier>) {
+        let barrier = Arc::new(Barrier::new(2));
+        let mut cable = MailboxCable::with_capacity(2, overflow);
+        cable.add_connection(vec![]);
+        let wait = barrier.clone();
+        cable
+            .post(move |_| {
+                wait.wait();
+            })
+            .unwrap();
+        // The worker is running the first message once the mailbox is empty
+        while cable.child.iter().any(|(_, x)| !x.queue.lock().jobs.is_empty()) {
+            std::thread::yield_now();
+        }
+        (cable, barrier)
+    }
+
+    fn post_all(cable: &MailboxCable<Vec<u32>>) -> Vec<bool> {
+        (1..=3).map(|i| cable.post(move |x| x.push(i)).is_ok()).collect()
+    }
+
+    #[test]
+    fn overflow() {
+        for (overflow, posted, handled) in [
+            (Overflow::DropOldest, [true; 3], vec![2, 3]),
+            (Overflow::DropNewest, [true; 3], vec![1, 2]),
+            (Overflow::Error, [true, true, false], vec![1, 2]),
+        ] {
+            let (cable, barrier) = blocked(overflow);
+            assert_eq!(post_all(&cable), posted);
+            assert_eq!(cable.dropped(), 1);
+            barrier.wait();
+            assert_eq!(cable.ask(|x| x.clone()), [handled]);
+        }
+        // Blocking waits for room
+        let (cable, barrier) = blocked(Overflow::Block);
+        let cable = Arc::new(cable);
+        let poster = {
+            let cable = cable.clone();
+            std::thread::spawn(move || post_all(&cable))
+        };
+        barrier.wait();
+        assert_eq!(poster.join().unwrap(), [true; 3]);
+        cable.flush();
+        assert_eq!(cable.ask(|x| x.clone()), [vec![1, 2, 3]]);
+        assert_eq!(cable.dropped(), 0);
+        assert_eq!(MailboxFull.to_string(), "the mailbox is full");
+    }
+
+    #[test]
+    fn context() {
+        let mut cable = MailboxCable::new();
+        cable.add_connection(None);
+        let headers = [(Headers::TRACE_ID, "1")].into_iter().collect();
+        CallContext::new(headers).scope(|| {
+            cable
+                .post(|x| *x = CallContext::current().get(Headers::TRACE_ID).map(str::to_string))
+                .unwrap()
+        });
+        assert_eq!(cable.ask(|x| x.take()), [Some("1".to_string())]);
+    }
+
+    #[test]
+    fn remove_drains() {
+        let mut cable = MailboxCable::new();
+        let id = cable.add_connection(0);
+        for _ in 0..100 {
+            cable.post(|x| *x += 1).unwrap();
+        }
+        assert_eq!(cable.remove_connection(id), Some(100));
+        assert!(cable.is_empty());
+        assert!(cable.ask(|x| *x).is_empty());
+    }
+}
@@ -0,0 +1,489 @@
+#![doc = concat! {r####"
+Forward procedure calls between clients connected.
+
+# Example: passive cable
+
+```
+"####,
+include_str!("../../examples/passive_cable_basic.rs"), r####"
+```
+"####}]
+
+use std::convert::Infallible;
+use std::fmt::{self, Debug, Display};
+use std::iter::{FlatMap, Map};
+use std::vec::IntoIter;
+
+use frincoe_rpc::Connection;
+
+mod balanced;
+pub use self::balanced::{BalancedCable, Candidate, LeastOutstanding, Picked, Policy, Random, RoundRobin, Weighted};
+
+mod bundle;
+pub use self::bundle::{Bundle, SingleError};
+
+mod dead_letter;
+pub use self::dead_letter::{DeadLetter, DeadLetterCable, DeadLetterPolicy, Undeliverable, Undelivered};
+
+mod dyn_cable;
+pub use self::dyn_cable::DynCable;
+
+mod failover;
+pub use self::failover::{FailoverCable, Outcome, Unavailable};
+
+mod hedged;
+pub use self::hedged::{CancelToken, HedgeStats, HedgedCable};
+
+mod mailbox;
+pub use self::mailbox::{MailboxCable, MailboxFull, Overflow};
+
+mod observed;
+pub use self::observed::{Observed, ObservedCall, Observer, Observers};
+
+mod reentrant;
+pub use self::reentrant::{Broadcasting, ReentrantCable, TooDeep};
+
+mod retained;
+pub use self::retained::{RetainedCable, Retention};
+
+mod shared;
+pub use self::shared::{SharedCable, Snapshot, SnapshotIntoIter};
+
+mod sharded;
+pub use self::sharded::ShardedCable;
+
+mod slot_map;
+use self::slot_map::SlotMap;
+pub use self::slot_map::{SlotEntriesMut, SlotIntoIter, SlotIterMut};
+
+mod static_cable;
+pub use self::static_cable::{Full, StaticCable};
+
+mod tree;
+pub use self::tree::{CablePath, PathError, Subtree, TreeCable};
+
+mod tuple;
+pub use self::tuple::TupleCable;
+
+mod voting;
+pub use self::voting::{Quorum, TieBreak, VoteOutcome, VotingCable};
+
+mod weak;
+pub use self::weak::WeakCable;
+
+
+
+/**
+Opaque handle of a connection added to a [`Cable`].
+
+An id is only meaningful to the cable returning it,
+and it won't refer to another connection after the connection is removed.
+*/
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ConnectionId {
+    index: u32,
+    generation: u32,
+}
+
+impl ConnectionId {
+    pub(crate) fn new(index: u32, generation: u32) -> Self {
+        Self { index, generation }
+    }
+
+    /// The id of the child at a fixed position, for cables whose children never change.
+    pub fn positional(index: u32) -> Self {
+        Self::new(index, 0)
+    }
+}
+
+/**
+Plug in servers and forward procedure calls between them, the passive version.
+
+Since not all passive cables is an active cable at the same time,
+this is the passive side of them.
+
+The clients don't have to be connections, e.g. plain trait objects;
+cables of clients which are connections are usually [`Connection`]s too, disconnecting all the clients.
+*/
+pub trait Cable {
+    /// Type of clients owned by the cable.
+    type Client;
+    /// Error of adding a connection.
+    type AddError;
+    /// An iterator over the children
+    type ChildIter<'a>: Iterator<Item = &'a mut Self::Client>
+    where
+        Self: 'a;
+    /// An iterator over the children and their ids.
+    type ChildIdIter<'a>: Iterator<Item = (ConnectionId, &'a mut Self::Client)>
+    where
+        Self: 'a;
+    /// Returns an iterator over the children.
+    fn iter_child(&mut self) -> Self::ChildIter<'_>;
+    /// Returns an iterator over the children and their ids.
+    fn iter_child_with_id(&mut self) -> Self::ChildIdIter<'_>;
+    /// Add a connection to the cable, returning its id.
+    /// If there's any error, the connection is not added.
+    fn add_connection(&mut self, addr: Self::Client) -> Result<ConnectionId, Self::AddError>;
+    /// Remove a connection from the cable, returning it if it's present.
+    ///
+    /// The connection is not disconnected, which is left to the caller.
+    fn remove_connection(&mut self, id: ConnectionId) -> Option<Self::Client>;
+    /// Get a connection in the cable by its id.
+    fn get_mut(&mut self, id: ConnectionId) -> Option<&mut Self::Client>;
+
+    /**
+    Call the children lazily, returning an iterator over the result of each child.
+
+    A child is called only when the next result is pulled from the iterator,
+    so the children after the one needed are not called at all.
+
+    ```
+    use frincoe::cable::{ArrayCable, Cable};
+    # use frincoe_rpc::Connection;
+    # struct Counter(i32);
+    # impl Connection for Counter {
+    #     type Error = ();
+    #     fn disconnect(&self) -> Result<(), ()> { Ok(()) }
+    # }
+
+    let mut cable = ArrayCable::<Counter>::from_iter([Counter(0), Counter(0), Counter(0)]);
+    let found = cable.call_each(|x| { x.0 += 1; x.0 }).find(|x| *x == 1);
+    assert_eq!(found, Some(1));
+    // Only the first child is called
+    assert_eq!(cable.iter_child().map(|x| x.0).collect::<Vec<_>>(), [1, 0, 0]);
+    ```
+    */
+    fn call_each<R, F>(&mut self, f: F) -> Map<Self::ChildIter<'_>, F>
+    where
+        F: FnMut(&mut Self::Client) -> R,
+        Self: Sized,
+    {
+        self.iter_child().map(f)
+    }
+
+    /**
+    Call the children lazily, returning an iterator over the items of their results.
+
+    This is like [`Cable::call_each`], but the results, usually [`Bundle`]s, are flattened;
+    the next child is called only when the items of the previous one are exhausted.
+    So `cable.stream(|x| x.method(args)).next()` calls as few children as needed to find an item.
+    */
+    fn stream<R, F>(&mut self, f: F) -> FlatMap<Self::ChildIter<'_>, R, F>
+    where
+        F: FnMut(&mut Self::Client) -> R,
+        R: IntoIterator,
+        Self: Sized,
+    {
+        self.iter_child().flat_map(f)
+    }
+}
+
+/**
+Errors reported by children of a cable, each with the id of the child reporting it.
+
+Usually returned when disconnecting a cable, which disconnects all its children
+and collects their errors instead of stopping at the first one.
+*/
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChildErrors<E> {
+    errors: Vec<(ConnectionId, E)>,
+}
+
+impl<E> ChildErrors<E> {
+    /// Create an empty list of errors.
+    pub fn new() -> Self {
+        Self { errors: vec![] }
+    }
+
+    /// Record an error of a child.
+    pub fn push(&mut self, id: ConnectionId, error: E) {
+        self.errors.push((id, error));
+    }
+
+    /// Amount of the errors.
+    pub fn len(&self) -> usize {
+        self.errors.len()
+    }
+
+    /// Check if there's no error.
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// Get the error of a child.
+    pub fn get(&self, id: ConnectionId) -> Option<&E> {
+        self.errors.iter().find(|(x, _)| *x == id).map(|(_, e)| e)
+    }
+
+    /// Iterate over the errors and the children reporting them.
+    pub fn iter(&self) -> impl Iterator<Item = &(ConnectionId, E)> {
+        self.errors.iter()
+    }
+
+    /// `Ok(())` if there's no error, or the errors otherwise.
+    pub fn into_result(self) -> Result<(), Self> {
+        if self.is_empty() {
+            Ok(())
+        } else {
+            Err(self)
+        }
+    }
+}
+
+impl<E> Default for ChildErrors<E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<E> FromIterator<(ConnectionId, E)> for ChildErrors<E> {
+    fn from_iter<R: IntoIterator<Item = (ConnectionId, E)>>(iter: R) -> Self {
+        Self {
+            errors: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl<E> IntoIterator for ChildErrors<E> {
+    type IntoIter = IntoIter<(ConnectionId, E)>;
+    type Item = (ConnectionId, E);
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.errors.into_iter()
+    }
+}
+
+impl<E: Display> Display for ChildErrors<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} of the children failed", self.errors.len())?;
+        for (id, error) in &self.errors {
+            write!(f, "; {:?}: {}", id, error)?;
+        }
+        Ok(())
+    }
+}
+
+impl<E: Debug + Display> std::error::Error for ChildErrors<E> {}
+
+
+
+/**
+Cable containing a list of clients of the same type.
+
+Ids of the clients are kept stable when other clients are removed,
+and the slots of removed clients are reused by clients added later.
+So the clients are iterated in the order they're added only if none is removed.
+
+Disconnecting the cable disconnects all the clients in it,
+and errors of the clients are collected into a [`ChildErrors`].
+To disconnect the cable automatically, wrap it into a [`DisconnectGuard`](frincoe_rpc::DisconnectGuard).
+ */
+#[derive(Clone, Default, Debug)]
+pub struct ArrayCable<T> {
+    child: SlotMap<T>,
+}
+
+impl<T> ArrayCable<T> {
+    /// Create an empty ArrayCable
+    pub fn new() -> Self {
+        Self { child: SlotMap::new() }
+    }
+
+    /// Amount of the clients.
+    pub fn len(&self) -> usize {
+        self.child.len()
+    }
+
+    /// Check if there's no client.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T, U: Into<T>> FromIterator<U> for ArrayCable<T> {
+    fn from_iter<R: IntoIterator<Item = U>>(iter: R) -> Self {
+        Self {
+            child: iter.into_iter().map(Into::<T>::into).collect(),
+        }
+    }
+}
+
+impl<T, U: Into<T>> Extend<U> for ArrayCable<T> {
+    fn extend<R: IntoIterator<Item = U>>(&mut self, iter: R) {
+        self.child.extend(iter.into_iter().map(|x| x.into()));
+    }
+
+    fn extend_one(&mut self, item: U) {
+        self.child.insert(item.into());
+    }
+
+    fn extend_reserve(&mut self, additional: usize) {
+        self.child.reserve(additional);
+    }
+}
+
+impl<T> IntoIterator for ArrayCable<T> {
+    type IntoIter = SlotIntoIter<T>;
+    type Item = T;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.child.into_iter()
+    }
+}
+
+impl<T: Connection> Connection for ArrayCable<T> {
+    type Error = ChildErrors<T::Error>;
+
+    fn disconnect(&self) -> Result<(), Self::Error> {
+        self.child
+            .iter()
+            .filter_map(|(id, x)| x.disconnect().err().map(|e| (id, e)))
+            .collect::<ChildErrors<_>>()
+            .into_result()
+    }
+}
+
+impl<T> Cable for ArrayCable<T> {
+    type AddError = Infallible;
+    type ChildIdIter<'a>
+        = SlotEntriesMut<'a, T>
+    where
+        T: 'a;
+    type ChildIter<'a>
+        = SlotIterMut<'a, T>
+    where
+        T: 'a;
+    type Client = T;
+
+    fn iter_child(&mut self) -> Self::ChildIter<'_> {
+        self.child.iter_mut()
+    }
+
+    fn iter_child_with_id(&mut self) -> Self::ChildIdIter<'_> {
+        self.child.entries_mut()
+    }
+
+    fn add_connection(&mut self, addr: Self::Client) -> Result<ConnectionId, Self::AddError> {
+        Ok(self.child.insert(addr))
+    }
+
+    fn remove_connection(&mut self, id: ConnectionId) -> Option<Self::Client> {
+        self.child.remove(id)
+    }
+
+    fn get_mut(&mut self, id: ConnectionId) -> Option<&mut Self::Client> {
+        self.child.get_mut(id)
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use frincoe_rpc::{Connection, DisconnectGuard};
+
+    use super::{ArrayCable, Bundle, Cable};
+
+    /// A client counting how many times it's disconnected, failing if `fail` is set.
+    #[derive(Debug, PartialEq)]
+    struct Client<'a> {
+        id: i32,
+        fail: bool,
+        closed: &'a Cell<i32>,
+    }
+
+    impl Connection for Client<'_> {
+        type Error = i32;
+
+        fn disconnect(&self) -> Result<(), Self::Error> {
+            self.closed.set(self.closed.get() + 1);
+            if self.fail {
+                Err(self.id)
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    #[test]
+    fn stable_ids() {
+        let closed = Cell::new(0);
+        let client = |id| Client {
+            id,
+            fail: false,
+            closed: &closed,
+        };
+        let ids = |cable: &mut ArrayCable<Client>| cable.iter_child().map(|x| x.id).collect::<Vec<_>>();
+        let mut cable = ArrayCable::new();
+        let a = cable.add_connection(client(1)).unwrap();
+        let b = cable.add_connection(client(2)).unwrap();
+        let c = cable.add_connection(client(3)).unwrap();
+        assert_eq!(cable.remove_connection(b), Some(client(2)));
+        assert_eq!(cable.remove_connection(b), None);
+        assert_eq!(cable.get_mut(b), None);
+        assert_eq!(cable.get_mut(a).map(|x| x.id), Some(1));
+        assert_eq!(cable.get_mut(c).map(|x| x.id), Some(3));
+        assert_eq!(ids(&mut cable), [1, 3]);
+        // The vacated slot is reused, but not the id
+        let d = cable.add_connection(client(4)).unwrap();
+        assert_ne!(b, d);
+        assert_eq!(cable.get_mut(b), None);
+        assert_eq!(cable.get_mut(d).map(|x| x.id), Some(4));
+        assert_eq!(cable.len(), 3);
+        assert_eq!(ids(&mut cable), [1, 4, 3]);
+    }
+
+    #[test]
+    fn cascade_disconnect() {
+        let closed = Cell::new(0);
+        let client = |id, fail| Client {
+            id,
+            fail,
+            closed: &closed,
+        };
+        let mut cable = ArrayCable::new();
+        cable.add_connection(client(1, false)).unwrap();
+        let failed = cable.add_connection(client(2, true)).unwrap();
+        cable.add_connection(client(3, false)).unwrap();
+        let errors = cable.disconnect().unwrap_err();
+        assert_eq!(closed.get(), 3);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors.get(failed), Some(&2));
+        // Guarded cables are disconnected on dropping
+        cable.remove_connection(failed);
+        drop(DisconnectGuard::new(cable));
+        assert_eq!(closed.get(), 5);
+    }
+
+    #[test]
+    fn streaming() {
+        let closed = Cell::new(0);
+        let client = |id| Client {
+            id,
+            fail: false,
+            closed: &closed,
+        };
+        let mut cable = ArrayCable::<Client>::from_iter([client(0), client(1), client(2), client(3)]);
+        let mut called = vec![];
+        let found = cable
+            .stream(|x| {
+                called.push(x.id);
+                Bundle::<i32, 2>::from_iter([x.id * 10, x.id * 10 + 1])
+            })
+            .find(|x| *x == 11);
+        assert_eq!(found, Some(11));
+        assert_eq!(called, [0, 1]);
+    }
+
+    #[test]
+    fn plain_children() {
+        // Clients which are not connections can still be used in a cable
+        let mut cable = ArrayCable::<Box<dyn FnMut(i32) -> i32>>::new();
+        cable.add_connection(Box::new(|x| x + 1)).unwrap();
+        cable.add_connection(Box::new(|x| x * 2)).unwrap();
+        assert_eq!(cable.call_each(|f| f(3)).collect::<Vec<_>>(), [4, 6]);
+    }
+}
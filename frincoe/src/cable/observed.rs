@@ -0,0 +1,370 @@
+use std::fmt::{self, Debug};
+use std::sync::Arc;
+
+use frincoe_rpc::Connection;
+
+use super::{Cable, ConnectionId};
+
+
+
+/**
+Observer of the lifecycle of the children of an [`Observed`] cable, e.g. for logs, metrics or cache invalidation.
+
+All the methods do nothing by default, so only the interesting events need to be implemented.
+*/
+pub trait Observer: Send + Sync {
+    /// Notified after a child is added.
+    fn on_connect(&self, _id: ConnectionId) {}
+    /// Notified after a child is removed, or disconnected along with the cable.
+    fn on_disconnect(&self, _id: ConnectionId) {}
+    /// Notified before a method of a child is called.
+    fn on_call_start(&self, _method: &str, _id: ConnectionId) {}
+    /// Notified after a method of a child returns or panics.
+    fn on_call_end(&self, _method: &str, _id: ConnectionId) {}
+}
+
+/**
+Observers attached to a cable, notified in the order they're attached.
+
+It's cheap to clone, and without any observer, notifying them costs nothing but a check.
+*/
+#[derive(Clone, Default)]
+pub struct Observers {
+    list: Option<Arc<Vec<Arc<dyn Observer>>>>,
+}
+
+impl Observers {
+    /// Create an empty list of observers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Amount of the observers.
+    pub fn len(&self) -> usize {
+        self.list.as_ref().map_or(0, |x| x.len())
+    }
+
+    /// Check if there's no observer.
+    pub fn is_empty(&self) -> bool {
+        self.list.is_none()
+    }
+
+    /// Attach an observer.
+    pub fn push(&mut self, observer: Arc<dyn Observer>) {
+        Arc::make_mut(self.list.get_or_insert_with(Default::default)).push(observer);
+    }
+
+    #[inline]
+    fn each(&self, f: impl Fn(&dyn Observer)) {
+        if let Some(list) = &self.list {
+            list.iter().for_each(|x| f(&**x));
+        }
+    }
+
+    /// Notify the observers that a child is added.
+    #[inline]
+    pub fn on_connect(&self, id: ConnectionId) {
+        self.each(|x| x.on_connect(id));
+    }
+
+    /// Notify the observers that a child is removed.
+    #[inline]
+    pub fn on_disconnect(&self, id: ConnectionId) {
+        self.each(|x| x.on_disconnect(id));
+    }
+
+    /// Notify the observers that a method of a child is to be called.
+    #[inline]
+    pub fn on_call_start(&self, method: &str, id: ConnectionId) {
+        self.each(|x| x.on_call_start(method, id));
+    }
+
+    /// Notify the observers that a method of a child returns.
+    #[inline]
+    pub fn on_call_end(&self, method: &str, id: ConnectionId) {
+        self.each(|x| x.on_call_end(method, id));
+    }
+
+    /// Notify the observers that a method of a child is to be called,
+    /// and that it returns when the guard is dropped, even if the call panics.
+    #[inline]
+    pub fn call<'a>(&'a self, method: &'a str, id: ConnectionId) -> ObservedCall<'a> {
+        self.on_call_start(method, id);
+        ObservedCall {
+            observers: self,
+            method,
+            id,
+        }
+    }
+}
+
+impl Debug for Observers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Observers").field("len", &self.len()).finish()
+    }
+}
+
+/// Guard of a call to a child returned by [`Observers::call`], notifying the end of the call when dropped.
+#[derive(Debug)]
+#[must_use = "the end of the call is notified when the guard is dropped"]
+pub struct ObservedCall<'a> {
+    observers: &'a Observers,
+    method: &'a str,
+    id: ConnectionId,
+}
+
+impl Drop for ObservedCall<'_> {
+    fn drop(&mut self) {
+        self.observers.on_call_end(self.method, self.id);
+    }
+}
+
+
+
+/**
+Cable wrapping another [`Cable`] to notify [`Observer`]s of its lifecycle.
+
+Adding and removing children through [`Cable::add_connection`] and [`Cable::remove_connection`]
+notifies [`Observer::on_connect`] and [`Observer::on_disconnect`],
+and disconnecting the cable notifies [`Observer::on_disconnect`] for each of its children;
+use the `observed` option of [`dispatch_sub`](frincoe_macros::dispatch_sub) to implement traits,
+so that [`Observer::on_call_start`] and [`Observer::on_call_end`] are notified around the call to each child.
+Changes made through [`Observed::inner_mut`] are not observed,
+so the children removed through it are still notified when the cable is disconnected.
+
+```
+use std::sync::{Arc, Mutex};
+
+use frincoe::cable::{ArrayCable, Bundle, Cable, ConnectionId, Observed, Observer};
+use frincoe_macros::{dispatch_sub, inject_implement};
+# use frincoe_rpc::Connection;
+
+trait Greet {
+    fn greet(&mut self) -> Bundle<String>;
+}
+
+struct Person(&'static str);
+impl Greet for Person {
+    fn greet(&mut self) -> Bundle<String> {
+        Bundle::from_single(format!("hi from {}", self.0))
+    }
+}
+# impl Connection for Person {
+#     type Error = ();
+#     fn disconnect(&self) -> Result<(), ()> { Ok(()) }
+# }
+
+#[derive(Default)]
+struct Log(Mutex<Vec<String>>);
+impl Observer for Log {
+    fn on_connect(&self, _: ConnectionId) {
+        self.0.lock().unwrap().push("connect".to_string());
+    }
+    fn on_call_end(&self, method: &str, _: ConnectionId) {
+        self.0.lock().unwrap().push(format!("{} called", method));
+    }
+}
+
+inject_implement! {
+    impl {
+        trait Greet {
+            fn greet(&mut self) -> Bundle<String>;
+        }
+    } for Observed<ArrayCable<Person>> in dispatch_sub(observed)
+}
+
+let log = Arc::new(Log::default());
+let mut cable = Observed::new(ArrayCable::new());
+cable.observe(log.clone());
+cable.add_connection(Person("a")).unwrap();
+assert_eq!(cable.greet(), ["hi from a"]);
+assert_eq!(*log.0.lock().unwrap(), ["connect", "greet called"]);
+```
+*/
+#[derive(Debug, Default)]
+pub struct Observed<C> {
+    cable: C,
+    /// Ids of the children added through the wrapper, in the order they're added.
+    ids: Vec<ConnectionId>,
+    observers: Observers,
+}
+
+impl<C> Observed<C> {
+    /// Wrap a cable without any observer.
+    pub fn new(cable: C) -> Self {
+        Self {
+            cable,
+            ids: vec![],
+            observers: Observers::new(),
+        }
+    }
+
+    /// Attach an observer.
+    pub fn observe(&mut self, observer: Arc<dyn Observer>) {
+        self.observers.push(observer);
+    }
+
+    /// The observers attached.
+    pub fn observers(&self) -> Observers {
+        self.observers.clone()
+    }
+
+    /// The wrapped cable.
+    pub fn inner(&self) -> &C {
+        &self.cable
+    }
+
+    /// The wrapped cable, changes made through which are not observed.
+    pub fn inner_mut(&mut self) -> &mut C {
+        &mut self.cable
+    }
+
+    /// Unwrap the cable, dropping the observers.
+    pub fn into_inner(self) -> C {
+        self.cable
+    }
+}
+
+impl<C: Connection> Connection for Observed<C> {
+    type Error = C::Error;
+
+    fn disconnect(&self) -> Result<(), Self::Error> {
+        let res = self.cable.disconnect();
+        for id in &self.ids {
+            self.observers.on_disconnect(*id);
+        }
+        res
+    }
+}
+
+impl<C: Cable> Cable for Observed<C> {
+    type AddError = C::AddError;
+    type ChildIdIter<'a>
+        = C::ChildIdIter<'a>
+    where
+        Self: 'a;
+    type ChildIter<'a>
+        = C::ChildIter<'a>
+    where
+        Self: 'a;
+    type Client = C::Client;
+
+    fn iter_child(&mut self) -> Self::ChildIter<'_> {
+        self.cable.iter_child()
+    }
+
+    fn iter_child_with_id(&mut self) -> Self::ChildIdIter<'_> {
+        self.cable.iter_child_with_id()
+    }
+
+    fn add_connection(&mut self, addr: Self::Client) -> Result<ConnectionId, Self::AddError> {
+        let id = self.cable.add_connection(addr)?;
+        self.ids.push(id);
+        self.observers.on_connect(id);
+        Ok(id)
+    }
+
+    fn remove_connection(&mut self, id: ConnectionId) -> Option<Self::Client> {
+        let client = self.cable.remove_connection(id)?;
+        self.ids.retain(|x| *x != id);
+        self.observers.on_disconnect(id);
+        Some(client)
+    }
+
+    fn get_mut(&mut self, id: ConnectionId) -> Option<&mut Self::Client> {
+        self.cable.get_mut(id)
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::panic::{self, AssertUnwindSafe};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use frincoe_rpc::{Connection, DisconnectGuard};
+
+    use super::{Observed, Observer, Observers};
+    use crate::cable::{ArrayCable, Cable, ConnectionId};
+
+    struct Child;
+
+    impl Connection for Child {
+        type Error = ();
+
+        fn disconnect(&self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    /// Count the children present.
+    #[derive(Default)]
+    struct Members(AtomicUsize);
+
+    impl Observer for Members {
+        fn on_connect(&self, _: ConnectionId) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+
+        fn on_disconnect(&self, _: ConnectionId) {
+            self.0.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn membership() {
+        let members = Arc::new(Members::default());
+        let mut cable = Observed::new(ArrayCable::new());
+        assert!(cable.observers().is_empty());
+        let unobserved = cable.add_connection(Child).unwrap();
+        cable.observe(members.clone());
+        cable.observe(members.clone());
+        let id = cable.add_connection(Child).unwrap();
+        assert_eq!(members.0.load(Ordering::Relaxed), 2);
+        cable.remove_connection(id).unwrap();
+        // Removing an absent child notifies nothing
+        assert!(cable.remove_connection(id).is_none());
+        assert_eq!(members.0.load(Ordering::Relaxed), 0);
+        cable.inner_mut().remove_connection(unobserved).unwrap();
+        assert_eq!(members.0.load(Ordering::Relaxed), 0);
+        assert!(cable.inner().is_empty());
+        // Disconnecting the cable disconnects each child
+        let mut cable = Observed::new(ArrayCable::new());
+        cable.observe(members.clone());
+        cable.add_connection(Child).unwrap();
+        cable.add_connection(Child).unwrap();
+        assert_eq!(members.0.load(Ordering::Relaxed), 2);
+        drop(DisconnectGuard::new(cable));
+        assert_eq!(members.0.load(Ordering::Relaxed), 0);
+    }
+
+    /// Count the calls running.
+    #[derive(Default)]
+    struct Running(AtomicUsize);
+
+    impl Observer for Running {
+        fn on_call_start(&self, _: &str, _: ConnectionId) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+
+        fn on_call_end(&self, _: &str, _: ConnectionId) {
+            self.0.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn call_panicked() {
+        let running = Arc::new(Running::default());
+        let mut observers = Observers::new();
+        observers.push(running.clone());
+        let res = panic::catch_unwind(AssertUnwindSafe(|| {
+            let _call = observers.call("f", ConnectionId::positional(0));
+            assert_eq!(running.0.load(Ordering::Relaxed), 1);
+            panic!("the child fails");
+        }));
+        assert!(res.is_err());
+        assert_eq!(running.0.load(Ordering::Relaxed), 0);
+    }
+}
@@ -0,0 +1,470 @@
+use std::collections::VecDeque;
+use std::fmt::{self, Debug, Display};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::thread::{self, ThreadId};
+
+use frincoe_rpc::{CallContext, Connection, Dispatcher};
+
+use super::slot_map::SlotMap;
+use super::{ChildErrors, ConnectionId};
+
+
+
+/// Error of a nested publish beyond the depth limit of a [`ReentrantCable`], which is dropped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TooDeep {
+    depth: usize,
+    limit: usize,
+}
+
+impl TooDeep {
+    /// Depth the publish would run at, 1 for a publish by the clients of a top-level publish.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// The depth limit of the cable.
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+}
+
+impl Display for TooDeep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "nested publish at depth {} exceeds the limit {}, which may be a feedback loop between the clients",
+            self.depth, self.limit
+        )
+    }
+}
+
+impl std::error::Error for TooDeep {}
+
+/// Error of changing the clients of a [`ReentrantCable`] by a client during its broadcast, holding the argument back.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Broadcasting<T>(T);
+
+impl<T> Broadcasting<T> {
+    /// Take the argument back, e.g. to add the client after the broadcast.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Debug for Broadcasting<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Broadcasting").finish_non_exhaustive()
+    }
+}
+
+impl<T> Display for Broadcasting<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "the clients of a ReentrantCable can't be changed during its broadcast"
+        )
+    }
+}
+
+impl<T> std::error::Error for Broadcasting<T> {}
+
+
+
+/// A nested publish waiting for the current broadcast.
+struct Nested<R> {
+    request: R,
+    depth: usize,
+    context: CallContext,
+}
+
+/// The broadcast running, and the publishes queued by it.
+struct Running<R, T> {
+    /// Thread running the broadcasts, with the clients locked.
+    runner: Option<ThreadId>,
+    /// Depth of the request being broadcast.
+    depth: usize,
+    queue: VecDeque<Nested<R>>,
+    /// Disconnection of the clients requested by a client, run after the broadcast.
+    disconnect: Option<fn(&SlotMap<T>)>,
+}
+
+/// Reset the running state when the runner finishes, even if a client panics,
+/// which drops the publishes queued and the disconnection requested.
+struct Finish<'a, R, T>(&'a Mutex<Running<R, T>>);
+
+impl<R, T> Drop for Finish<'_, R, T> {
+    fn drop(&mut self) {
+        let mut running = lock(self.0);
+        running.runner = None;
+        running.depth = 0;
+        running.queue.clear();
+        running.disconnect = None;
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+
+
+/**
+Cable shared between threads, whose clients can publish to it while handling a publish.
+
+A client reacting to a request by publishing to the same cable is rejected by the borrow rules with `&mut` cables,
+and recurses without bound with shared ones.
+This cable runs each broadcast to completion instead:
+a publish by a client during a broadcast is queued, and run after the current broadcast in the order of publishing,
+with the [`CallContext`] of the publisher.
+The chain of the nested publishes is limited by a depth, 16 by default;
+a publish beyond the limit is dropped and returns a [`TooDeep`] error to the publisher,
+and counted by [`ReentrantCable::too_deep`], so that feedback loops between the clients are noticed.
+The responses to the nested publishes are dropped, as their publishers have returned by then;
+and if a client panics, the panic goes to the top-level publisher, and the publishes still queued are dropped.
+
+The clients are locked during a broadcast, so a client adding or removing clients gets a [`Broadcasting`] error,
+holding the argument back to retry after the broadcast; [`ReentrantCable::len`] can still be read.
+A client disconnecting the cable defers the disconnection until the nested publishes are completed,
+as if it were a publish queued last; the panic of a client drops it with the rest of the queue.
+
+The requests are the ones of [`make_dispatcher`](frincoe_macros::make_dispatcher),
+so the clients should be [`Dispatcher`]s and the request type should be `Clone`;
+the clients usually hold a [`Weak`](std::sync::Weak) reference to the cable to publish to it.
+Publishes from other threads wait until the current broadcast and its nested publishes are completed.
+
+```
+use std::sync::{Arc, Mutex, Weak};
+use frincoe::cable::ReentrantCable;
+use frincoe_macros::make_dispatcher;
+
+/// Retry a failed job, which fails again and again.
+struct Retrier {
+    cable: Weak<ReentrantCable<Retrier>>,
+    log: Arc<Mutex<Vec<String>>>,
+}
+impl Retrier {
+    fn failed(&mut self, job: u32, attempt: usize) {
+        self.log.lock().unwrap().push(format!("{} failed {}", job, attempt));
+        let cable = self.cable.upgrade().unwrap();
+        if let Err(e) = cable.publish(Request::Failed(job, attempt + 1)) {
+            self.log.lock().unwrap().push(format!("gave up: {}", e.depth()));
+        }
+    }
+}
+
+make_dispatcher!(#[derive(Clone)] impl {
+    trait Jobs {
+        fn failed(&mut self, job: u32, attempt: usize);
+    }
+} for Retrier as Request -> Response);
+
+let log = Arc::new(Mutex::new(vec![]));
+let cable = Arc::new(ReentrantCable::with_max_depth(2));
+cable.add_connection(Retrier { cable: Arc::downgrade(&cable), log: log.clone() }).unwrap();
+assert!(cable.publish(Request::Failed(7, 0)).unwrap().is_some());
+assert_eq!(*log.lock().unwrap(), ["7 failed 0", "7 failed 1", "7 failed 2", "gave up: 3"]);
+assert_eq!(cable.too_deep(), 1);
+```
+*/
+pub struct ReentrantCable<T: Dispatcher> {
+    child: Mutex<SlotMap<T>>,
+    running: Mutex<Running<T::Request, T>>,
+    /// Amount of the clients, readable during a broadcast.
+    len: AtomicUsize,
+    max_depth: usize,
+    too_deep: AtomicU64,
+}
+
+impl<T: Dispatcher> Default for ReentrantCable<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Dispatcher> ReentrantCable<T> {
+    /// Create an empty ReentrantCable, allowing nested publishes up to depth 16.
+    pub fn new() -> Self {
+        Self::with_max_depth(16)
+    }
+
+    /// Create an empty ReentrantCable, allowing nested publishes up to the depth.
+    ///
+    /// With depth 0, all the nested publishes are rejected.
+    pub fn with_max_depth(max_depth: usize) -> Self {
+        Self {
+            child: Mutex::new(SlotMap::new()),
+            running: Mutex::new(Running {
+                runner: None,
+                depth: 0,
+                queue: VecDeque::new(),
+                disconnect: None,
+            }),
+            len: AtomicUsize::new(0),
+            max_depth,
+            too_deep: AtomicU64::new(0),
+        }
+    }
+
+    /// The depth limit of the nested publishes.
+    pub fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    /// Amount of the nested publishes dropped by the depth limit so far.
+    pub fn too_deep(&self) -> u64 {
+        self.too_deep.load(Ordering::Relaxed)
+    }
+
+    /// Amount of the clients.
+    pub fn len(&self) -> usize {
+        self.len.load(Ordering::Acquire)
+    }
+
+    /// Check if there's no client.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Add a client, returning its id, or the client back if it's added by a client during a broadcast.
+    pub fn add_connection(&self, addr: T) -> Result<ConnectionId, Broadcasting<T>> {
+        if self.is_running() {
+            return Err(Broadcasting(addr));
+        }
+        let mut child = lock(&self.child);
+        let id = child.insert(addr);
+        self.len.store(child.len(), Ordering::Release);
+        Ok(id)
+    }
+
+    /// Remove a client, returning it if it's present, or the id back if it's removed by a client during a broadcast.
+    ///
+    /// The client is not disconnected, which is left to the caller.
+    pub fn remove_connection(&self, id: ConnectionId) -> Result<Option<T>, Broadcasting<ConnectionId>> {
+        if self.is_running() {
+            return Err(Broadcasting(id));
+        }
+        let mut child = lock(&self.child);
+        let client = child.remove(id);
+        self.len.store(child.len(), Ordering::Release);
+        Ok(client)
+    }
+
+    /// Check if the current thread is running a broadcast.
+    fn is_running(&self) -> bool {
+        lock(&self.running).runner == Some(thread::current().id())
+    }
+}
+
+impl<T: Dispatcher> ReentrantCable<T>
+where
+    T::Request: Clone,
+{
+    /**
+    Publish a request to all the clients.
+
+    A top-level publish returns the responses of the clients,
+    after the broadcast and all the publishes nested in it are completed;
+    a publish by a client during a broadcast is queued and returns `None`,
+    and the responses to it are dropped.
+    */
+    pub fn publish(&self, request: T::Request) -> Result<Option<Vec<T::Response>>, TooDeep> {
+        let current = thread::current().id();
+        {
+            let mut running = lock(&self.running);
+            if running.runner == Some(current) {
+                let depth = running.depth + 1;
+                if depth > self.max_depth {
+                    self.too_deep.fetch_add(1, Ordering::Relaxed);
+                    return Err(TooDeep {
+                        depth,
+                        limit: self.max_depth,
+                    });
+                }
+                running.queue.push_back(Nested {
+                    request,
+                    depth,
+                    context: CallContext::current(),
+                });
+                return Ok(None);
+            }
+        }
+
+        let mut child = lock(&self.child);
+        lock(&self.running).runner = Some(current);
+        let _finish = Finish(&self.running);
+        let res = child.iter_mut().map(|x| x.dispatch(request.clone())).collect();
+        loop {
+            let nested = {
+                let mut running = lock(&self.running);
+                let nested = running.queue.pop_front();
+                if let Some(nested) = &nested {
+                    running.depth = nested.depth;
+                }
+                nested
+            };
+            let Some(Nested { request, context, .. }) = nested else {
+                break;
+            };
+            context.scope(|| {
+                for x in child.iter_mut() {
+                    x.dispatch(request.clone());
+                }
+            });
+        }
+        let disconnect = lock(&self.running).disconnect.take();
+        if let Some(disconnect) = disconnect {
+            disconnect(&child);
+        }
+        Ok(Some(res))
+    }
+}
+
+impl<T: Dispatcher + Connection> Connection for ReentrantCable<T> {
+    type Error = ChildErrors<T::Error>;
+
+    /**
+    Disconnect all the clients.
+
+    A client disconnecting the cable during a broadcast can't reach the other clients, which are locked,
+    so the disconnection is deferred until the broadcast and its nested publishes are completed,
+    and `Ok(())` is returned at once; the errors of the deferred disconnection are dropped,
+    as the client requesting it has returned by then.
+    */
+    fn disconnect(&self) -> Result<(), Self::Error> {
+        {
+            let mut running = lock(&self.running);
+            if running.runner == Some(thread::current().id()) {
+                running.disconnect = Some(|child| {
+                    for (_, x) in child.iter() {
+                        let _ = x.disconnect();
+                    }
+                });
+                return Ok(());
+            }
+        }
+        lock(&self.child)
+            .iter()
+            .filter_map(|(id, x)| x.disconnect().err().map(|e| (id, e)))
+            .collect::<ChildErrors<_>>()
+            .into_result()
+    }
+}
+
+impl<T: Dispatcher> Debug for ReentrantCable<T>
+where
+    T: Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReentrantCable")
+            .field("child", &self.child)
+            .field("max_depth", &self.max_depth)
+            .field("too_deep", &self.too_deep)
+            .finish()
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex, Weak};
+
+    use frincoe_rpc::{Connection, Dispatcher};
+
+    use super::{Broadcasting, ReentrantCable};
+    use crate::cable::ConnectionId;
+
+    /// A child publishing the half of each even request, and recording the requests.
+    struct Halve {
+        cable: Weak<ReentrantCable<Halve>>,
+        log: Arc<Mutex<Vec<u32>>>,
+    }
+
+    impl Dispatcher for Halve {
+        type Request = u32;
+        type Response = u32;
+
+        fn dispatch(&mut self, request: u32) -> u32 {
+            self.log.lock().unwrap().push(request);
+            if request.is_multiple_of(2) {
+                let nested = self.cable.upgrade().unwrap().publish(request / 2);
+                assert_eq!(nested, Ok(None));
+            }
+            request
+        }
+    }
+
+    #[test]
+    fn run_to_completion() {
+        let log = Arc::new(Mutex::new(vec![]));
+        let cable = Arc::new(ReentrantCable::new());
+        for _ in 0..2 {
+            cable
+                .add_connection(Halve {
+                    cable: Arc::downgrade(&cable),
+                    log: log.clone(),
+                })
+                .unwrap();
+        }
+        assert_eq!(cable.publish(4), Ok(Some(vec![4, 4])));
+        // Each nested publish runs after the broadcast publishing it, once for each publisher
+        assert_eq!(*log.lock().unwrap(), [4, 4, 2, 2, 2, 2, 1, 1, 1, 1, 1, 1, 1, 1]);
+        assert_eq!(cable.too_deep(), 0);
+        // The cable is usable again after the broadcast
+        log.lock().unwrap().clear();
+        assert_eq!(cable.publish(3), Ok(Some(vec![3, 3])));
+        assert_eq!(*log.lock().unwrap(), [3, 3]);
+    }
+
+    #[test]
+    fn change_during_broadcast() {
+        /// A child adding another one, removing itself and disconnecting the cable,
+        /// handing the changes to the caller when refused, and counting its disconnections.
+        struct Join(Weak<ReentrantCable<Join>>, ConnectionId, Arc<AtomicUsize>);
+
+        impl Dispatcher for Join {
+            type Request = ();
+            type Response = (usize, Option<Join>, Option<ConnectionId>);
+
+            fn dispatch(&mut self, _: ()) -> Self::Response {
+                let cable = self.0.upgrade().unwrap();
+                let added = cable
+                    .add_connection(Join(self.0.clone(), self.1, self.2.clone()))
+                    .err()
+                    .map(Broadcasting::into_inner);
+                let removed = cable.remove_connection(self.1).err().map(Broadcasting::into_inner);
+                // Deferred until the broadcast is completed
+                assert_eq!(cable.disconnect(), Ok(()));
+                assert_eq!(self.2.load(Ordering::Relaxed), 0);
+                (cable.len(), added, removed)
+            }
+        }
+
+        impl Connection for Join {
+            type Error = ();
+
+            fn disconnect(&self) -> Result<(), Self::Error> {
+                self.2.fetch_add(1, Ordering::Relaxed);
+                Ok(())
+            }
+        }
+
+        let disconnected = Arc::new(AtomicUsize::new(0));
+        let cable = Arc::new(ReentrantCable::new());
+        let id = ConnectionId::positional(0);
+        let join = Join(Arc::downgrade(&cable), id, disconnected.clone());
+        assert_eq!(cable.add_connection(join).unwrap(), id);
+        let mut res = cable.publish(()).unwrap().unwrap();
+        assert_eq!(disconnected.load(Ordering::Relaxed), 1);
+        let (len, added, removed) = res.pop().unwrap();
+        assert_eq!(len, 1);
+        assert_eq!(removed, Some(id));
+        // The changes can be retried after the broadcast
+        cable.add_connection(added.unwrap()).unwrap();
+        assert!(cable.remove_connection(id).unwrap().is_some());
+        assert_eq!(cable.len(), 1);
+    }
+}
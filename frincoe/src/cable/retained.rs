@@ -0,0 +1,396 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::VecDeque;
+use std::convert::Infallible;
+use std::fmt::{self, Debug};
+use std::hash::{Hash, Hasher};
+use std::time::{Duration, Instant};
+
+use frincoe_rpc::{Connection, Dispatcher};
+
+use super::slot_map::{SlotEntriesMut, SlotIterMut, SlotMap};
+use super::{Cable, ChildErrors, ConnectionId};
+
+
+
+/// Measure of the size of a request in bytes.
+type Measure<R> = Box<dyn Fn(&R) -> usize + Send + Sync>;
+/// Hashed key of a request.
+type KeyOf<R> = Box<dyn Fn(&R) -> u64 + Send + Sync>;
+
+/**
+Which requests a [`RetainedCable`] keeps to replay, by count, age or byte size.
+
+The limits are combined, so a request is kept only if it's within all of them.
+With a key, only the last request of each key is kept, e.g. the last call of each method.
+*/
+pub struct Retention<R> {
+    count: Option<usize>,
+    age: Option<Duration>,
+    bytes: Option<(usize, Measure<R>)>,
+    key: Option<KeyOf<R>>,
+    /// Clock of the ages, replaced by the tests.
+    now: fn() -> Instant,
+}
+
+impl<R> Retention<R> {
+    /// Keep the last requests up to the count.
+    pub fn last(count: usize) -> Self {
+        Self {
+            count: Some(count),
+            age: None,
+            bytes: None,
+            key: None,
+            now: Instant::now,
+        }
+    }
+
+    /// Keep the last request of each key, without any limit on the count.
+    pub fn per_key<K: Hash>(key: impl Fn(&R) -> K + Send + Sync + 'static) -> Self {
+        Self {
+            count: None,
+            age: None,
+            bytes: None,
+            key: Some(Box::new(move |x| {
+                let mut hasher = DefaultHasher::new();
+                key(x).hash(&mut hasher);
+                hasher.finish()
+            })),
+            now: Instant::now,
+        }
+    }
+
+    /// Keep the requests up to the count.
+    pub fn max_count(mut self, count: usize) -> Self {
+        self.count = Some(count);
+        self
+    }
+
+    /// Keep the requests no older than the age.
+    pub fn max_age(mut self, age: Duration) -> Self {
+        self.age = Some(age);
+        self
+    }
+
+    /// Keep the requests up to the total size in bytes, measured by `size`.
+    pub fn max_bytes(mut self, bytes: usize, size: impl Fn(&R) -> usize + Send + Sync + 'static) -> Self {
+        self.bytes = Some((bytes, Box::new(size)));
+        self
+    }
+}
+
+impl<R> Debug for Retention<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Retention")
+            .field("count", &self.count)
+            .field("age", &self.age)
+            .field("bytes", &self.bytes.as_ref().map(|(x, _)| x))
+            .field("keyed", &self.key.is_some())
+            .finish()
+    }
+}
+
+/// A request kept to replay.
+#[derive(Debug)]
+struct Retained<R> {
+    request: R,
+    at: Instant,
+    bytes: usize,
+    key: Option<u64>,
+}
+
+
+
+/**
+Cable keeping the recent requests, and replaying them into each child added later.
+
+The requests are the ones of [`make_dispatcher`](frincoe_macros::make_dispatcher),
+so the children should be [`Dispatcher`]s and the request type should be `Clone`,
+e.g. generated with `make_dispatcher!(#[derive(Clone)] impl ...)`.
+The cable is a [`Dispatcher`] too, recording each request by the [`Retention`] and dispatching it to all the children;
+a child added by [`Cable::add_connection`] is replayed the requests kept before it receives any live one,
+which fits state-like topics where a late subscriber needs the latest state.
+
+```
+use frincoe::cable::{Cable, RetainedCable, Retention};
+use frincoe_macros::make_dispatcher;
+use frincoe_rpc::Dispatcher;
+# use frincoe_rpc::Connection;
+
+#[derive(Default)]
+struct Panel(Vec<String>);
+impl Panel {
+    fn show(&mut self, room: String, celsius: i32) {
+        self.0.push(format!("{}: {}", room, celsius));
+    }
+}
+# impl Connection for Panel {
+#     type Error = ();
+#     fn disconnect(&self) -> Result<(), ()> { Ok(()) }
+# }
+
+make_dispatcher!(#[derive(Clone)] impl {
+    trait Temperature {
+        fn show(&mut self, room: String, celsius: i32);
+    }
+} for Panel as Request -> Response);
+
+// Keep the latest temperature of each room
+let mut cable = RetainedCable::with_retention(Retention::per_key(|x| match x {
+    Request::Show(room, _) => room.clone(),
+}));
+cable.dispatch(Request::Show("kitchen".to_string(), 20));
+cable.dispatch(Request::Show("hall".to_string(), 18));
+cable.dispatch(Request::Show("kitchen".to_string(), 22));
+let id = cable.add_connection(Panel::default()).unwrap();
+cable.dispatch(Request::Show("hall".to_string(), 19));
+assert_eq!(cable.get_mut(id).unwrap().0, ["hall: 18", "kitchen: 22", "hall: 19"]);
+```
+*/
+pub struct RetainedCable<T: Dispatcher> {
+    child: SlotMap<T>,
+    retained: VecDeque<Retained<T::Request>>,
+    retention: Retention<T::Request>,
+    bytes: usize,
+}
+
+impl<T: Dispatcher> Default for RetainedCable<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Dispatcher> RetainedCable<T> {
+    /// Create an empty RetainedCable, keeping the last request.
+    pub fn new() -> Self {
+        Self::with_retention(Retention::last(1))
+    }
+
+    /// Create an empty RetainedCable, keeping the requests by the retention.
+    pub fn with_retention(retention: Retention<T::Request>) -> Self {
+        Self {
+            child: SlotMap::new(),
+            retained: VecDeque::new(),
+            retention,
+            bytes: 0,
+        }
+    }
+
+    /// Amount of the clients.
+    pub fn len(&self) -> usize {
+        self.child.len()
+    }
+
+    /// Check if there's no client.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The requests kept to replay, from the oldest one, without the ones older than the age limit.
+    pub fn retained(&self) -> impl Iterator<Item = &T::Request> {
+        let now = (self.retention.now)();
+        let age = self.retention.age;
+        self.retained
+            .iter()
+            .filter(move |x| age.is_none_or(|age| now.duration_since(x.at) <= age))
+            .map(|x| &x.request)
+    }
+
+    /// Forget all the requests kept.
+    pub fn clear(&mut self) {
+        self.retained.clear();
+        self.bytes = 0;
+    }
+
+    /// Keep a request, dropping the ones out of the retention.
+    fn retain(&mut self, request: T::Request) {
+        let key = self.retention.key.as_ref().map(|f| f(&request));
+        if key.is_some() {
+            let mut replaced = 0;
+            self.retained.retain(|x| {
+                let same = x.key == key;
+                if same {
+                    replaced += x.bytes;
+                }
+                !same
+            });
+            self.bytes -= replaced;
+        }
+        let bytes = self.retention.bytes.as_ref().map_or(0, |(_, f)| f(&request));
+        self.bytes += bytes;
+        self.retained.push_back(Retained {
+            request,
+            at: (self.retention.now)(),
+            bytes,
+            key,
+        });
+        self.expire();
+    }
+
+    /// Drop the oldest requests out of the retention.
+    fn expire(&mut self) {
+        let now = (self.retention.now)();
+        while let Some(oldest) = self.retained.front() {
+            let expired = self.retention.count.is_some_and(|x| self.retained.len() > x)
+                || self.retention.age.is_some_and(|x| now.duration_since(oldest.at) > x)
+                || self.retention.bytes.as_ref().is_some_and(|(x, _)| self.bytes > *x);
+            if !expired {
+                break;
+            }
+            self.bytes -= oldest.bytes;
+            self.retained.pop_front();
+        }
+    }
+}
+
+impl<T: Dispatcher> Dispatcher for RetainedCable<T>
+where
+    T::Request: Clone,
+{
+    type Request = T::Request;
+    type Response = Vec<T::Response>;
+
+    /// Keep the request, and dispatch it to all the children.
+    fn dispatch(&mut self, request: Self::Request) -> Self::Response {
+        self.retain(request.clone());
+        self.child.iter_mut().map(|x| x.dispatch(request.clone())).collect()
+    }
+}
+
+impl<T: Dispatcher + Connection> Connection for RetainedCable<T> {
+    type Error = ChildErrors<T::Error>;
+
+    fn disconnect(&self) -> Result<(), Self::Error> {
+        self.child
+            .iter()
+            .filter_map(|(id, x)| x.disconnect().err().map(|e| (id, e)))
+            .collect::<ChildErrors<_>>()
+            .into_result()
+    }
+}
+
+impl<T: Dispatcher> Cable for RetainedCable<T>
+where
+    T::Request: Clone,
+{
+    type AddError = Infallible;
+    type ChildIdIter<'a>
+        = SlotEntriesMut<'a, T>
+    where
+        Self: 'a;
+    type ChildIter<'a>
+        = SlotIterMut<'a, T>
+    where
+        Self: 'a;
+    type Client = T;
+
+    fn iter_child(&mut self) -> Self::ChildIter<'_> {
+        self.child.iter_mut()
+    }
+
+    fn iter_child_with_id(&mut self) -> Self::ChildIdIter<'_> {
+        self.child.entries_mut()
+    }
+
+    /// Add a child after replaying the requests kept into it.
+    fn add_connection(&mut self, mut addr: Self::Client) -> Result<ConnectionId, Self::AddError> {
+        self.expire();
+        for retained in &self.retained {
+            addr.dispatch(retained.request.clone());
+        }
+        Ok(self.child.insert(addr))
+    }
+
+    fn remove_connection(&mut self, id: ConnectionId) -> Option<Self::Client> {
+        self.child.remove(id)
+    }
+
+    fn get_mut(&mut self, id: ConnectionId) -> Option<&mut Self::Client> {
+        self.child.get_mut(id)
+    }
+}
+
+impl<T: Dispatcher> Debug for RetainedCable<T>
+where
+    T: Debug,
+    T::Request: Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RetainedCable")
+            .field("child", &self.child)
+            .field("retained", &self.retained)
+            .field("retention", &self.retention)
+            .finish()
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::time::{Duration, Instant};
+
+    use frincoe_rpc::Dispatcher;
+
+    use super::{RetainedCable, Retention};
+
+    /// A child recording the requests.
+    #[derive(Default)]
+    struct Log(Vec<String>);
+
+    impl Dispatcher for Log {
+        type Request = String;
+        type Response = ();
+
+        fn dispatch(&mut self, request: Self::Request) {
+            self.0.push(request);
+        }
+    }
+
+    thread_local! {
+        static START: Instant = Instant::now();
+        static ELAPSED: Cell<Duration> = Cell::default();
+    }
+
+    /// A clock advanced by the tests.
+    fn now() -> Instant {
+        START.with(|x| *x) + ELAPSED.with(Cell::get)
+    }
+
+    fn retained(retention: Retention<String>, requests: &[&str]) -> Vec<String> {
+        let mut cable = RetainedCable::<Log>::with_retention(retention);
+        for x in requests {
+            cable.dispatch(x.to_string());
+        }
+        cable.retained().cloned().collect()
+    }
+
+    #[test]
+    fn retention() {
+        assert_eq!(retained(Retention::last(2), &["a", "b", "c"]), ["b", "c"]);
+        assert_eq!(
+            retained(Retention::per_key(|x: &String| x.len()), &["a", "bb", "c", "dd", "eee"]),
+            ["c", "dd", "eee"]
+        );
+        assert_eq!(
+            retained(Retention::last(10).max_bytes(4, String::len), &["a", "bb", "c", "dd"]),
+            ["c", "dd"]
+        );
+        assert_eq!(
+            retained(Retention::last(10).max_bytes(2, String::len), &["a", "bbb"]),
+            Vec::<String>::new()
+        );
+        let mut retention = Retention::last(10).max_age(Duration::from_secs(50));
+        retention.now = now;
+        let mut cable = RetainedCable::<Log>::with_retention(retention);
+        cable.dispatch("old".to_string());
+        ELAPSED.with(|x| x.set(Duration::from_secs(50)));
+        cable.dispatch("kept".to_string());
+        assert_eq!(cable.retained().collect::<Vec<_>>(), ["old", "kept"]);
+        ELAPSED.with(|x| x.set(Duration::from_secs(80)));
+        // Listed by the age even before being expired by a dispatch
+        assert_eq!(cable.retained().collect::<Vec<_>>(), ["kept"]);
+        cable.dispatch("new".to_string());
+        assert_eq!(cable.retained().collect::<Vec<_>>(), ["kept", "new"]);
+    }
+}
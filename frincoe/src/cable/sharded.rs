@@ -0,0 +1,229 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::hash::{BuildHasher, BuildHasherDefault, Hash};
+
+use frincoe_rpc::Connection;
+
+use super::slot_map::{SlotEntriesMut, SlotIterMut, SlotMap};
+use super::{Cable, ChildErrors, ConnectionId};
+
+
+
+/**
+Cable partitioning keys across its children with consistent hashing.
+
+Each child is placed at several points of a hash ring, and a key belongs to the first child after its hash on the ring,
+so adding or removing a child only moves about `1 / N` of the keys, i.e. those of the child itself.
+Use the `shard` option of [`dispatch_sub`](frincoe_macros::dispatch_sub) to call the child owning an argument;
+the cable is also a [`Cable`], so calls without the option still broadcast to all the children.
+
+The hasher `S` decides the positions of the children and the keys,
+the default one is deterministic, so the keys are partitioned the same way by every cable.
+
+```
+use frincoe::cable::{Bundle, Cable, ShardedCable};
+use frincoe_macros::{dispatch_sub, inject_implement};
+# use frincoe_rpc::Connection;
+
+trait Cache {
+    fn get(&mut self, user_id: u64) -> Bundle<String>;
+}
+
+struct Worker(&'static str);
+impl Cache for Worker {
+    fn get(&mut self, user_id: u64) -> Bundle<String> {
+        Bundle::from_single(format!("{} has {}", self.0, user_id))
+    }
+}
+# impl Connection for Worker {
+#     type Error = ();
+#     fn disconnect(&self) -> Result<(), ()> { Ok(()) }
+# }
+
+inject_implement! {
+    impl {
+        trait Cache {
+            fn get(&mut self, user_id: u64) -> Bundle<String>;
+        }
+    } for ShardedCable<Worker> in dispatch_sub(shard = user_id)
+}
+
+let mut cable = ShardedCable::new();
+cable.add_connection(Worker("a")).unwrap();
+cable.add_connection(Worker("b")).unwrap();
+// A key is always routed to the same single child
+let owner = cable.get(42).into_single().unwrap();
+assert_eq!(cable.get(42), [owner]);
+```
+*/
+#[derive(Clone, Debug)]
+pub struct ShardedCable<T, S = BuildHasherDefault<DefaultHasher>> {
+    child: SlotMap<T>,
+    /// Points of the children on the hash ring.
+    ring: BTreeMap<u64, ConnectionId>,
+    replicas: u32,
+    hasher: S,
+}
+
+impl<T, S: Default> Default for ShardedCable<T, S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, S: Default> ShardedCable<T, S> {
+    /// Create an empty ShardedCable, placing each child at 64 points of the ring.
+    pub fn new() -> Self {
+        Self::with_replicas(64)
+    }
+
+    /// Create an empty ShardedCable, placing each child at the given amount of points of the ring.
+    ///
+    /// More points spread the keys more evenly, but make adding and removing children slower.
+    pub fn with_replicas(replicas: u32) -> Self {
+        Self::with_hasher(replicas, S::default())
+    }
+}
+
+impl<T, S> ShardedCable<T, S> {
+    /// Create an empty ShardedCable with the hasher.
+    pub fn with_hasher(replicas: u32, hasher: S) -> Self {
+        assert!(replicas > 0, "a child should be placed at least once");
+        Self {
+            child: SlotMap::new(),
+            ring: BTreeMap::new(),
+            replicas,
+            hasher,
+        }
+    }
+
+    /// Amount of the clients.
+    pub fn len(&self) -> usize {
+        self.child.len()
+    }
+
+    /// Check if there's no client.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T, S: BuildHasher> ShardedCable<T, S> {
+    /// The id of the child owning the key.
+    pub fn shard_id<K: Hash + ?Sized>(&self, key: &K) -> Option<ConnectionId> {
+        let hash = self.hasher.hash_one(key);
+        let (_, id) = self.ring.range(hash..).next().or_else(|| self.ring.iter().next())?;
+        Some(*id)
+    }
+
+    /// Get the child owning the key.
+    pub fn shard_child<K: Hash + ?Sized>(&mut self, key: &K) -> Option<(ConnectionId, &mut T)> {
+        let id = self.shard_id(key)?;
+        self.child.get_mut(id).map(|x| (id, x))
+    }
+
+    /// Points of a child on the ring.
+    fn points(&self, id: ConnectionId) -> impl Iterator<Item = u64> + '_ {
+        (0..self.replicas).map(move |replica| self.hasher.hash_one((id, replica)))
+    }
+}
+
+impl<T: Connection, S> Connection for ShardedCable<T, S> {
+    type Error = ChildErrors<T::Error>;
+
+    fn disconnect(&self) -> Result<(), Self::Error> {
+        self.child
+            .iter()
+            .filter_map(|(id, x)| x.disconnect().err().map(|e| (id, e)))
+            .collect::<ChildErrors<_>>()
+            .into_result()
+    }
+}
+
+impl<T, S: BuildHasher> Cable for ShardedCable<T, S> {
+    type AddError = Infallible;
+    type ChildIdIter<'a>
+        = SlotEntriesMut<'a, T>
+    where
+        Self: 'a;
+    type ChildIter<'a>
+        = SlotIterMut<'a, T>
+    where
+        Self: 'a;
+    type Client = T;
+
+    fn iter_child(&mut self) -> Self::ChildIter<'_> {
+        self.child.iter_mut()
+    }
+
+    fn iter_child_with_id(&mut self) -> Self::ChildIdIter<'_> {
+        self.child.entries_mut()
+    }
+
+    fn add_connection(&mut self, addr: Self::Client) -> Result<ConnectionId, Self::AddError> {
+        let id = self.child.insert(addr);
+        let points = self.points(id).collect::<Vec<_>>();
+        self.ring.extend(points.into_iter().map(|x| (x, id)));
+        Ok(id)
+    }
+
+    fn remove_connection(&mut self, id: ConnectionId) -> Option<Self::Client> {
+        let client = self.child.remove(id)?;
+        self.ring.retain(|_, x| *x != id);
+        Some(client)
+    }
+
+    fn get_mut(&mut self, id: ConnectionId) -> Option<&mut Self::Client> {
+        self.child.get_mut(id)
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use frincoe_rpc::Connection;
+
+    use super::ShardedCable;
+    use crate::cable::Cable;
+
+    struct Shard;
+
+    impl Connection for Shard {
+        type Error = ();
+
+        fn disconnect(&self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn consistent() {
+        let mut cable = ShardedCable::<Shard>::new();
+        assert_eq!(cable.shard_id(&0), None);
+        let ids = (0..10)
+            .map(|_| cable.add_connection(Shard).unwrap())
+            .collect::<Vec<_>>();
+        let owners = |cable: &ShardedCable<Shard>| (0..1000).map(|x| cable.shard_id(&x).unwrap()).collect::<Vec<_>>();
+        let before = owners(&cable);
+        assert!(ids.iter().all(|id| before.contains(id)));
+        // Only the keys of the new child are moved
+        let added = cable.add_connection(Shard).unwrap();
+        let after = owners(&cable);
+        let moved = before.iter().zip(&after).filter(|(x, y)| x != y).collect::<Vec<_>>();
+        assert!(moved.iter().all(|(_, y)| **y == added));
+        assert!(!moved.is_empty() && moved.len() < 200);
+        // Only the keys of the removed child are moved back
+        cable.remove_connection(ids[3]);
+        let removed = owners(&cable);
+        for ((x, y), z) in before.iter().zip(&after).zip(&removed) {
+            if *y != ids[3] {
+                assert_eq!(y, z);
+            }
+            if *x != ids[3] && *y != added {
+                assert_eq!(x, z);
+            }
+        }
+    }
+}
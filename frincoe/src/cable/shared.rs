@@ -0,0 +1,306 @@
+use core::iter::FusedIterator;
+use core::ops::Range;
+use std::sync::{Arc, Mutex};
+
+use arc_swap::ArcSwap;
+use frincoe_rpc::Connection;
+
+use super::slot_map::SlotMap;
+use super::{ChildErrors, ConnectionId};
+
+
+
+/// The clients of a [`SharedCable`] at some moment.
+type Children<T> = Vec<(ConnectionId, Arc<T>)>;
+
+/**
+Cable shared between threads, whose clients can be added and removed during broadcasting.
+
+The clients are kept in an immutable snapshot, which is replaced as a whole when a client is added or removed;
+taking a snapshot is lock-free, so broadcasting never waits for the changes of the clients,
+and a broadcast only sees the clients in the snapshot taken when it's started.
+Adding or removing a client copies the list of the clients, so they're expected to change much less often than calls.
+
+Since the clients are shared by the snapshots, they're called through `&self` and held in [`Arc`]s,
+so this is not a [`Cable`](super::Cable);
+it provides `iter_child` and `iter_child_with_id` with `&self`,
+so that [`dispatch_sub`](frincoe_macros::dispatch_sub) can still be used on it for methods taking `&self`.
+
+```
+use std::sync::Arc;
+use std::thread;
+use frincoe::cable::{Bundle, SharedCable};
+use frincoe_macros::{dispatch_sub, inject_implement};
+
+trait Greet {
+    fn hello(&self, name: &str) -> Bundle<String>;
+}
+
+struct Greeter(&'static str);
+impl Greet for Greeter {
+    fn hello(&self, name: &str) -> Bundle<String> {
+        Bundle::from_single(format!("{} {}", self.0, name))
+    }
+}
+
+inject_implement! {
+    impl {
+        trait Greet {
+            fn hello(&self, name: &str) -> Bundle<String>;
+        }
+    } for SharedCable<Greeter> in dispatch_sub
+}
+
+let cable = Arc::new(SharedCable::new());
+cable.add_connection(Greeter("hello"));
+let joining = {
+    let cable = cable.clone();
+    thread::spawn(move || cable.add_connection(Greeter("hi")))
+};
+let joined = joining.join().unwrap();
+assert_eq!(cable.hello("world"), ["hello world", "hi world"]);
+cable.remove_connection(joined);
+assert_eq!(cable.hello("world"), ["hello world"]);
+```
+*/
+#[derive(Debug)]
+pub struct SharedCable<T> {
+    child: ArcSwap<Children<T>>,
+    /// Ids of the clients, locked when changing the clients.
+    ids: Mutex<SlotMap<()>>,
+}
+
+impl<T> Default for SharedCable<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> SharedCable<T> {
+    /// Create an empty SharedCable
+    pub fn new() -> Self {
+        Self {
+            child: ArcSwap::from_pointee(vec![]),
+            ids: Mutex::new(SlotMap::new()),
+        }
+    }
+
+    /// Amount of the clients at the moment.
+    pub fn len(&self) -> usize {
+        self.child.load().len()
+    }
+
+    /// Check if there's no client at the moment.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Take a snapshot of the clients, which is not affected by later changes.
+    pub fn snapshot(&self) -> Snapshot<T> {
+        Snapshot {
+            child: self.child.load_full(),
+        }
+    }
+
+    /// Returns an iterator over the clients in a snapshot.
+    pub fn iter_child(&self) -> impl Iterator<Item = Arc<T>> {
+        self.iter_child_with_id().map(|(_, x)| x)
+    }
+
+    /// Returns an iterator over the clients and their ids in a snapshot.
+    pub fn iter_child_with_id(&self) -> SnapshotIntoIter<T> {
+        self.snapshot().into_iter()
+    }
+
+    /// Add a client, returning its id.
+    ///
+    /// Broadcasts already started don't call the client.
+    pub fn add_connection(&self, addr: T) -> ConnectionId {
+        let mut ids = self.ids.lock().unwrap_or_else(|e| e.into_inner());
+        let id = ids.insert(());
+        let mut child = Children::clone(&self.child.load());
+        child.push((id, Arc::new(addr)));
+        self.child.store(Arc::new(child));
+        id
+    }
+
+    /// Remove a client, returning it if it's present.
+    ///
+    /// Broadcasts already started may still call the client,
+    /// and the client is not disconnected, which is left to the caller.
+    pub fn remove_connection(&self, id: ConnectionId) -> Option<Arc<T>> {
+        let mut ids = self.ids.lock().unwrap_or_else(|e| e.into_inner());
+        ids.remove(id)?;
+        let mut child = Children::clone(&self.child.load());
+        let index = child.iter().position(|(x, _)| *x == id)?;
+        let (_, removed) = child.remove(index);
+        self.child.store(Arc::new(child));
+        Some(removed)
+    }
+}
+
+impl<T: Connection> Connection for SharedCable<T> {
+    type Error = ChildErrors<T::Error>;
+
+    fn disconnect(&self) -> Result<(), Self::Error> {
+        self.snapshot().disconnect()
+    }
+}
+
+
+
+/// The clients of a [`SharedCable`] at some moment, see [`SharedCable::snapshot`].
+#[derive(Debug)]
+pub struct Snapshot<T> {
+    child: Arc<Children<T>>,
+}
+
+impl<T> Clone for Snapshot<T> {
+    fn clone(&self) -> Self {
+        Self {
+            child: self.child.clone(),
+        }
+    }
+}
+
+impl<T> Snapshot<T> {
+    /// Amount of the clients.
+    pub fn len(&self) -> usize {
+        self.child.len()
+    }
+
+    /// Check if there's no client.
+    pub fn is_empty(&self) -> bool {
+        self.child.is_empty()
+    }
+
+    /// Get a client by its id.
+    pub fn get(&self, id: ConnectionId) -> Option<&T> {
+        self.child.iter().find(|(x, _)| *x == id).map(|(_, x)| &**x)
+    }
+
+    /// Returns an iterator over the clients.
+    pub fn iter_child(&self) -> impl Iterator<Item = &T> {
+        self.child.iter().map(|(_, x)| &**x)
+    }
+
+    /// Returns an iterator over the clients and their ids.
+    pub fn iter_child_with_id(&self) -> impl Iterator<Item = (ConnectionId, &T)> {
+        self.child.iter().map(|(id, x)| (*id, &**x))
+    }
+}
+
+impl<T> IntoIterator for Snapshot<T> {
+    type IntoIter = SnapshotIntoIter<T>;
+    type Item = (ConnectionId, Arc<T>);
+
+    fn into_iter(self) -> Self::IntoIter {
+        SnapshotIntoIter {
+            range: 0..self.child.len(),
+            child: self.child,
+        }
+    }
+}
+
+impl<T: Connection> Connection for Snapshot<T> {
+    type Error = ChildErrors<T::Error>;
+
+    fn disconnect(&self) -> Result<(), Self::Error> {
+        self.iter_child_with_id()
+            .filter_map(|(id, x)| x.disconnect().err().map(|e| (id, e)))
+            .collect::<ChildErrors<_>>()
+            .into_result()
+    }
+}
+
+
+
+/// Iterator over the clients and their ids in a snapshot of a [`SharedCable`].
+#[derive(Debug)]
+pub struct SnapshotIntoIter<T> {
+    child: Arc<Children<T>>,
+    range: Range<usize>,
+}
+
+impl<T> Iterator for SnapshotIntoIter<T> {
+    type Item = (ConnectionId, Arc<T>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.range.next().map(|i| self.child[i].clone())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.range.size_hint()
+    }
+}
+
+impl<T> DoubleEndedIterator for SnapshotIntoIter<T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.range.next_back().map(|i| self.child[i].clone())
+    }
+}
+
+impl<T> ExactSizeIterator for SnapshotIntoIter<T> {}
+
+impl<T> FusedIterator for SnapshotIntoIter<T> {}
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Barrier;
+    use std::thread;
+
+    use super::SharedCable;
+
+    #[test]
+    fn snapshots() {
+        let cable = SharedCable::new();
+        let a = cable.add_connection(1);
+        let snapshot = cable.snapshot();
+        let b = cable.add_connection(2);
+        assert_eq!(cable.remove_connection(a).as_deref(), Some(&1));
+        assert_eq!(cable.remove_connection(a), None);
+        // The snapshot is not affected
+        assert_eq!(snapshot.iter_child().collect::<Vec<_>>(), [&1]);
+        assert_eq!(snapshot.get(a), Some(&1));
+        assert_eq!(cable.snapshot().get(b), Some(&2));
+        // The id of a removed client is not reused
+        let c = cable.add_connection(3);
+        assert_ne!(a, c);
+        assert_eq!(cable.iter_child().map(|x| *x).collect::<Vec<_>>(), [2, 3]);
+    }
+
+    #[test]
+    fn concurrent() {
+        let cable = SharedCable::new();
+        let calls = AtomicUsize::new(0);
+        let barrier = Barrier::new(2);
+        thread::scope(|s| {
+            s.spawn(|| {
+                for i in 0..100 {
+                    let id = cable.add_connection(i);
+                    if i == 0 {
+                        barrier.wait();
+                    }
+                    if i % 2 == 1 {
+                        cable.remove_connection(id);
+                    }
+                }
+            });
+            barrier.wait();
+            // Broadcasting during subscribing sees consistent snapshots
+            for _ in 0..100 {
+                let snapshot = cable.snapshot();
+                let values = snapshot.iter_child().copied().collect::<Vec<_>>();
+                assert_eq!(values.len(), snapshot.len());
+                assert_eq!(values.first(), Some(&0));
+                calls.fetch_add(values.len(), Ordering::Relaxed);
+            }
+        });
+        assert!(calls.load(Ordering::Relaxed) >= 100);
+        assert_eq!(cable.len(), 50);
+        assert!(cable.iter_child().all(|x| *x % 2 == 0));
+    }
+}
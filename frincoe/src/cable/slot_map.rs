@@ -0,0 +1,293 @@
+use core::iter::{Enumerate, FusedIterator};
+use core::slice;
+use std::vec;
+
+use super::ConnectionId;
+
+
+
+/// A slot holding a value, the generation is increased every time the slot is vacated.
+#[derive(Clone, Debug)]
+pub(crate) struct Slot<T> {
+    generation: u32,
+    value: Option<T>,
+}
+
+impl<T> Slot<T> {
+    pub fn vacant() -> Self {
+        Self {
+            generation: 0,
+            value: None,
+        }
+    }
+
+    pub fn is_vacant(&self) -> bool {
+        self.value.is_none()
+    }
+
+    /// Put a value into the vacant slot, returning its id.
+    pub fn put(&mut self, index: u32, value: T) -> ConnectionId {
+        debug_assert!(self.is_vacant());
+        self.value = Some(value);
+        ConnectionId::new(index, self.generation)
+    }
+
+    /// Take the value if it's of the same generation as the id.
+    pub fn take(&mut self, id: ConnectionId) -> Option<T> {
+        if self.generation != id.generation {
+            return None;
+        }
+        let value = self.value.take()?;
+        self.generation = self.generation.wrapping_add(1);
+        Some(value)
+    }
+
+    pub fn get(&self, id: ConnectionId) -> Option<&T> {
+        self.value.as_ref().filter(|_| self.generation == id.generation)
+    }
+
+    pub fn get_mut(&mut self, id: ConnectionId) -> Option<&mut T> {
+        self.value.as_mut().filter(|_| self.generation == id.generation)
+    }
+
+    pub fn entry(&self, index: usize) -> Option<(ConnectionId, &T)> {
+        let id = ConnectionId::new(index as u32, self.generation);
+        self.value.as_ref().map(|x| (id, x))
+    }
+
+    pub fn entry_mut(&mut self, index: usize) -> Option<(ConnectionId, &mut T)> {
+        let id = ConnectionId::new(index as u32, self.generation);
+        self.value.as_mut().map(|x| (id, x))
+    }
+}
+
+/**
+Storage with stable ids across removals, used by cables to hold their children.
+
+Vacant slots are reused by later insertions, with a different generation,
+so an id of a removed value never refers to a later one.
+Values are iterated in the order of their slots, i.e. the insertion order if nothing is removed.
+*/
+#[derive(Clone, Debug)]
+pub(crate) struct SlotMap<T> {
+    slots: Vec<Slot<T>>,
+    free: Vec<u32>,
+}
+
+impl<T> Default for SlotMap<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> SlotMap<T> {
+    pub fn new() -> Self {
+        Self {
+            slots: vec![],
+            free: vec![],
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.len() - self.free.len()
+    }
+
+    pub fn reserve(&mut self, additional: usize) {
+        self.slots.reserve(additional.saturating_sub(self.free.len()));
+    }
+
+    pub fn insert(&mut self, value: T) -> ConnectionId {
+        match self.free.pop() {
+            Some(index) => self.slots[index as usize].put(index, value),
+            None => {
+                let index = u32::try_from(self.slots.len()).expect("too many slots");
+                self.slots.push(Slot::vacant());
+                self.slots[index as usize].put(index, value)
+            }
+        }
+    }
+
+    pub fn remove(&mut self, id: ConnectionId) -> Option<T> {
+        let value = self.slots.get_mut(id.index as usize)?.take(id)?;
+        self.free.push(id.index);
+        Some(value)
+    }
+
+    pub fn get(&self, id: ConnectionId) -> Option<&T> {
+        self.slots.get(id.index as usize)?.get(id)
+    }
+
+    pub fn get_mut(&mut self, id: ConnectionId) -> Option<&mut T> {
+        self.slots.get_mut(id.index as usize)?.get_mut(id)
+    }
+
+    /// Remove the values not satisfying the predicate, returning how many are removed.
+    pub fn retain(&mut self, mut f: impl FnMut(ConnectionId, &mut T) -> bool) -> usize {
+        let mut removed = 0;
+        for (index, slot) in self.slots.iter_mut().enumerate() {
+            if let Some((id, value)) = slot.entry_mut(index) {
+                if !f(id, value) {
+                    slot.take(id);
+                    self.free.push(index as u32);
+                    removed += 1;
+                }
+            }
+        }
+        removed
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (ConnectionId, &T)> {
+        self.slots
+            .iter()
+            .enumerate()
+            .filter_map(|(index, slot)| slot.entry(index))
+    }
+
+    pub fn iter_mut(&mut self) -> SlotIterMut<'_, T> {
+        SlotIterMut::new(&mut self.slots)
+    }
+
+    pub fn entries_mut(&mut self) -> SlotEntriesMut<'_, T> {
+        SlotEntriesMut::new(&mut self.slots)
+    }
+}
+
+impl<T> IntoIterator for SlotMap<T> {
+    type IntoIter = SlotIntoIter<T>;
+    type Item = T;
+
+    fn into_iter(self) -> Self::IntoIter {
+        SlotIntoIter {
+            slots: self.slots.into_iter(),
+        }
+    }
+}
+
+impl<T> FromIterator<T> for SlotMap<T> {
+    fn from_iter<R: IntoIterator<Item = T>>(iter: R) -> Self {
+        let mut res = Self::new();
+        res.extend(iter);
+        res
+    }
+}
+
+impl<T> Extend<T> for SlotMap<T> {
+    fn extend<R: IntoIterator<Item = T>>(&mut self, iter: R) {
+        for value in iter {
+            self.insert(value);
+        }
+    }
+}
+
+
+
+/// Mutable iterator over the values of a cable using slots to store its children.
+#[derive(Debug)]
+pub struct SlotIterMut<'a, T> {
+    slots: slice::IterMut<'a, Slot<T>>,
+}
+
+impl<'a, T> SlotIterMut<'a, T> {
+    pub(crate) fn new(slots: &'a mut [Slot<T>]) -> Self {
+        Self {
+            slots: slots.iter_mut(),
+        }
+    }
+}
+
+impl<'a, T> Iterator for SlotIterMut<'a, T> {
+    type Item = &'a mut T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.slots.find_map(|slot| slot.value.as_mut())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.slots.size_hint().1)
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for SlotIterMut<'a, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(value) = self.slots.next_back()?.value.as_mut() {
+                return Some(value);
+            }
+        }
+    }
+}
+
+impl<'a, T> FusedIterator for SlotIterMut<'a, T> {}
+
+
+
+/// Mutable iterator over the values and their ids of a cable using slots to store its children.
+#[derive(Debug)]
+pub struct SlotEntriesMut<'a, T> {
+    slots: Enumerate<slice::IterMut<'a, Slot<T>>>,
+}
+
+impl<'a, T> SlotEntriesMut<'a, T> {
+    pub(crate) fn new(slots: &'a mut [Slot<T>]) -> Self {
+        Self {
+            slots: slots.iter_mut().enumerate(),
+        }
+    }
+}
+
+impl<'a, T> Iterator for SlotEntriesMut<'a, T> {
+    type Item = (ConnectionId, &'a mut T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.slots.find_map(|(index, slot)| slot.entry_mut(index))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.slots.size_hint().1)
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for SlotEntriesMut<'a, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        loop {
+            let (index, slot) = self.slots.next_back()?;
+            if let Some(entry) = slot.entry_mut(index) {
+                return Some(entry);
+            }
+        }
+    }
+}
+
+impl<'a, T> FusedIterator for SlotEntriesMut<'a, T> {}
+
+
+
+/// Iterator moving the values out of a cable using slots to store its children.
+#[derive(Debug)]
+pub struct SlotIntoIter<T> {
+    slots: vec::IntoIter<Slot<T>>,
+}
+
+impl<T> Iterator for SlotIntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.slots.find_map(|slot| slot.value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.slots.size_hint().1)
+    }
+}
+
+impl<T> DoubleEndedIterator for SlotIntoIter<T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(value) = self.slots.next_back()?.value {
+                return Some(value);
+            }
+        }
+    }
+}
+
+impl<T> FusedIterator for SlotIntoIter<T> {}
@@ -0,0 +1,199 @@
+use std::fmt::{self, Debug, Display};
+
+use frincoe_rpc::Connection;
+
+use super::slot_map::{Slot, SlotEntriesMut, SlotIterMut};
+use super::{Cable, ChildErrors, ConnectionId};
+
+
+
+/// Error of adding a connection to a full [`StaticCable`], holding the rejected connection back.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Full<T>(T);
+
+impl<T> Full<T> {
+    /// Take the rejected connection back.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Display for Full<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("the cable is full")
+    }
+}
+
+impl<T: Debug> std::error::Error for Full<T> {}
+
+
+
+/**
+Cable containing at most `N` clients of the same type, without allocating on the heap.
+
+This acts like [`ArrayCable`](super::ArrayCable),
+but the clients are stored in a fixed array inside the cable,
+and adding a connection to a full cable fails with [`Full`].
+Adding a connection costs `O(N)` to find a vacant slot, so `N` is expected to be small.
+
+```
+use frincoe::cable::{Cable, StaticCable};
+# use frincoe_rpc::Connection;
+# #[derive(Debug)]
+# struct Client(i32);
+# impl Connection for Client {
+#     type Error = ();
+#     fn disconnect(&self) -> Result<(), ()> { Ok(()) }
+# }
+
+let mut cable = StaticCable::<Client, 2>::new();
+let first = cable.add_connection(Client(1)).unwrap();
+cable.add_connection(Client(2)).unwrap();
+assert_eq!(cable.add_connection(Client(3)).unwrap_err().into_inner().0, 3);
+cable.remove_connection(first);
+cable.add_connection(Client(3)).unwrap();
+assert_eq!(cable.iter_child().map(|x| x.0).collect::<Vec<_>>(), [3, 2]);
+```
+*/
+#[derive(Clone, Debug)]
+pub struct StaticCable<T, const N: usize> {
+    child: [Slot<T>; N],
+}
+
+impl<T, const N: usize> StaticCable<T, N> {
+    /// Create an empty StaticCable
+    pub fn new() -> Self {
+        Self {
+            child: std::array::from_fn(|_| Slot::vacant()),
+        }
+    }
+
+    /// Amount of the clients.
+    pub fn len(&self) -> usize {
+        self.child.iter().filter(|x| !x.is_vacant()).count()
+    }
+
+    /// Check if there's no client.
+    pub fn is_empty(&self) -> bool {
+        self.child.iter().all(Slot::is_vacant)
+    }
+
+    /// Check if no more client can be added.
+    pub fn is_full(&self) -> bool {
+        !self.child.iter().any(Slot::is_vacant)
+    }
+
+    /// Maximum amount of the clients, i.e. `N`.
+    pub fn capacity(&self) -> usize {
+        N
+    }
+}
+
+impl<T, const N: usize> Default for StaticCable<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Connection, const N: usize> Connection for StaticCable<T, N> {
+    type Error = ChildErrors<T::Error>;
+
+    fn disconnect(&self) -> Result<(), Self::Error> {
+        self.child
+            .iter()
+            .enumerate()
+            .filter_map(|(index, slot)| slot.entry(index))
+            .filter_map(|(id, x)| x.disconnect().err().map(|e| (id, e)))
+            .collect::<ChildErrors<_>>()
+            .into_result()
+    }
+}
+
+impl<T, const N: usize> Cable for StaticCable<T, N> {
+    type AddError = Full<T>;
+    type ChildIdIter<'a>
+        = SlotEntriesMut<'a, T>
+    where
+        T: 'a;
+    type ChildIter<'a>
+        = SlotIterMut<'a, T>
+    where
+        T: 'a;
+    type Client = T;
+
+    fn iter_child(&mut self) -> Self::ChildIter<'_> {
+        SlotIterMut::new(&mut self.child)
+    }
+
+    fn iter_child_with_id(&mut self) -> Self::ChildIdIter<'_> {
+        SlotEntriesMut::new(&mut self.child)
+    }
+
+    fn add_connection(&mut self, addr: Self::Client) -> Result<ConnectionId, Self::AddError> {
+        match self.child.iter().position(Slot::is_vacant) {
+            Some(index) => Ok(self.child[index].put(index as u32, addr)),
+            None => Err(Full(addr)),
+        }
+    }
+
+    fn remove_connection(&mut self, id: ConnectionId) -> Option<Self::Client> {
+        self.child.get_mut(id.index as usize)?.take(id)
+    }
+
+    fn get_mut(&mut self, id: ConnectionId) -> Option<&mut Self::Client> {
+        self.child.get_mut(id.index as usize)?.get_mut(id)
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use frincoe_rpc::Connection;
+
+    use super::{Full, StaticCable};
+    use crate::cable::{Cable, ConnectionId};
+
+    /// A child failing to disconnect if it's odd.
+    #[derive(Debug, PartialEq, Eq)]
+    struct Child(u32);
+
+    impl Connection for Child {
+        type Error = u32;
+
+        fn disconnect(&self) -> Result<(), Self::Error> {
+            match self.0 % 2 {
+                0 => Ok(()),
+                _ => Err(self.0),
+            }
+        }
+    }
+
+    #[test]
+    fn full() {
+        let mut cable = StaticCable::<Child, 2>::new();
+        assert_eq!(cable.add_connection(Child(0)), Ok(ConnectionId::positional(0)));
+        assert_eq!(cable.add_connection(Child(1)), Ok(ConnectionId::positional(1)));
+        assert!(cable.is_full());
+        assert_eq!(cable.add_connection(Child(2)), Err(Full(Child(2))));
+        assert_eq!(cable.len(), 2);
+        assert_eq!(Full(()).to_string(), "the cable is full");
+    }
+
+    #[test]
+    fn reuse_slot() {
+        let mut cable = StaticCable::<Child, 2>::new();
+        let first = cable.add_connection(Child(0)).unwrap();
+        cable.add_connection(Child(1)).unwrap();
+        assert_eq!(cable.remove_connection(first), Some(Child(0)));
+        let reused = cable.add_connection(Child(2)).unwrap();
+        assert_eq!(reused.index, first.index);
+        assert_ne!(reused, first);
+        // The old id doesn't reach the new child
+        assert!(cable.get_mut(first).is_none());
+        assert_eq!(cable.get_mut(reused), Some(&mut Child(2)));
+        assert_eq!(cable.iter_child().map(|x| x.0).collect::<Vec<_>>(), [2, 1]);
+        let errors = cable.disconnect().unwrap_err();
+        assert_eq!(errors.iter().collect::<Vec<_>>(), [&(ConnectionId::positional(1), 1)]);
+    }
+}
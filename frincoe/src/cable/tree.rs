@@ -0,0 +1,610 @@
+use std::collections::btree_map::{IterMut, RangeMut, ValuesMut};
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::fmt::{self, Display};
+use std::iter::{Chain, Map};
+use std::ops::Bound;
+use std::str::FromStr;
+
+use frincoe_rpc::Connection;
+
+use super::slot_map::SlotMap;
+use super::{Bundle, Cable, ChildErrors, ConnectionId};
+
+
+
+/// Errors of addressing clients in a [`TreeCable`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PathError {
+    /// The path doesn't start with `/`.
+    NotAbsolute,
+    /// The path contains an empty segment, e.g. `/a//b` or `/a/`.
+    EmptySegment,
+    /// There's already a client at the path, or below it.
+    Occupied,
+    /// The path is below a client, which can't have children.
+    UnderLeaf,
+    /// The client has no path, which is required below a node.
+    NoPath,
+}
+
+impl Display for PathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::NotAbsolute => "the path should start with `/`",
+            Self::EmptySegment => "the path contains an empty segment",
+            Self::Occupied => "the path is already occupied",
+            Self::UnderLeaf => "the path is below a client",
+            Self::NoPath => "the client has no path",
+        })
+    }
+}
+
+impl std::error::Error for PathError {}
+
+
+
+/**
+An absolute path of a node in a [`TreeCable`], e.g. `/site-a/rack-3/node-7`.
+
+The root is `/`, and other paths are segments each led by a `/`.
+Paths are ordered segment by segment, so a node is always followed by the nodes below it.
+*/
+#[derive(Clone, Default, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CablePath {
+    segments: Vec<String>,
+}
+
+impl CablePath {
+    /// The path of the root.
+    pub fn root() -> Self {
+        Self::default()
+    }
+
+    /// Parse and validate a path.
+    pub fn new(path: &str) -> Result<Self, PathError> {
+        let rest = path.strip_prefix('/').ok_or(PathError::NotAbsolute)?;
+        if rest.is_empty() {
+            return Ok(Self::root());
+        }
+        let segments = rest.split('/').map(String::from).collect::<Vec<_>>();
+        if segments.iter().any(String::is_empty) {
+            return Err(PathError::EmptySegment);
+        }
+        Ok(Self { segments })
+    }
+
+    /// Iterate over the segments of the path.
+    pub fn segments(&self) -> impl Iterator<Item = &str> {
+        self.segments.iter().map(String::as_str)
+    }
+
+    /// Amount of the segments, i.e. the depth of the node.
+    pub fn depth(&self) -> usize {
+        self.segments.len()
+    }
+
+    /// Check if this is the root.
+    pub fn is_root(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    /// Check if the node is `other` or below it.
+    pub fn starts_with(&self, other: &Self) -> bool {
+        self.segments.starts_with(&other.segments)
+    }
+
+    /// The path of `other` relative to this node.
+    pub fn join(&self, other: &Self) -> Self {
+        Self {
+            segments: self.segments.iter().chain(&other.segments).cloned().collect(),
+        }
+    }
+
+    /// The path of the parent node, or `None` for the root.
+    pub fn parent(&self) -> Option<Self> {
+        let (_, parent) = self.segments.split_last()?;
+        Some(Self {
+            segments: parent.to_vec(),
+        })
+    }
+}
+
+impl FromStr for CablePath {
+    type Err = PathError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::new(s)
+    }
+}
+
+impl TryFrom<&str> for CablePath {
+    type Error = PathError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        Self::new(value)
+    }
+}
+
+impl TryFrom<String> for CablePath {
+    type Error = PathError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::new(&value)
+    }
+}
+
+impl Display for CablePath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_root() {
+            return f.write_str("/");
+        }
+        for segment in &self.segments {
+            write!(f, "/{}", segment)?;
+        }
+        Ok(())
+    }
+}
+
+
+
+/**
+Cable of clients addressed by paths, forming a tree of cables.
+
+Clients are the leaves of the tree, and the nodes above them are addressed by the prefixes of their paths,
+so a call can be broadcast to all the clients, or to those below a node with [`TreeCable::subtree`],
+and a single client can be addressed by its path with [`TreeCable::get_by_path`].
+Clients added with [`Cable::add_connection`] have no path,
+they're only reached by broadcasting to the whole tree or by their ids.
+
+The ids of the clients are unique in the whole tree, also in the subtrees,
+so the `attributed` option of [`dispatch_sub`](frincoe_macros::dispatch_sub) records the clients producing the items,
+whose paths are looked up by [`TreeCable::path`] or [`TreeCable::resolve_paths`].
+
+```
+use frincoe::cable::{Bundle, Cable, Subtree, TreeCable};
+use frincoe_macros::{dispatch_sub, inject_implement};
+# use frincoe_rpc::Connection;
+
+trait Probe {
+    fn load(&mut self) -> Bundle<u32>;
+}
+
+struct Node(u32);
+impl Probe for Node {
+    fn load(&mut self) -> Bundle<u32> {
+        Bundle::from_single(self.0)
+    }
+}
+# impl Connection for Node {
+#     type Error = ();
+#     fn disconnect(&self) -> Result<(), ()> { Ok(()) }
+# }
+
+inject_implement! {
+    impl {
+        trait Probe {
+            fn load(&mut self) -> Bundle<u32>;
+        }
+    } for TreeCable<Node> in dispatch_sub(attributed)
+}
+inject_implement! {
+    impl {
+        trait Probe {
+            fn load(&mut self) -> Bundle<u32>;
+        }
+    } for Subtree<'_, Node> in dispatch_sub(attributed)
+}
+
+let mut tree = TreeCable::new();
+tree.insert("/site-a/rack-3/node-7", Node(70)).unwrap();
+tree.insert("/site-a/rack-3/node-8", Node(80)).unwrap();
+tree.insert("/site-b/rack-1/node-1", Node(10)).unwrap();
+
+// Broadcast to all the clients
+assert_eq!(tree.load(), [70, 80, 10]);
+// Broadcast below a node, recording the paths
+let loads = tree.subtree("/site-a").unwrap().load();
+let paths = tree.resolve_paths(loads).into_iter().map(|(path, x)| (path.unwrap().to_string(), x));
+assert_eq!(
+    paths.collect::<Vec<_>>(),
+    [("/site-a/rack-3/node-7".to_string(), 70), ("/site-a/rack-3/node-8".to_string(), 80)],
+);
+// Unicast to an address
+assert_eq!(tree.get_by_path("/site-b/rack-1/node-1").unwrap().load(), [10]);
+```
+*/
+#[derive(Clone, Debug)]
+pub struct TreeCable<T> {
+    /// Paths of the clients by their ids, `None` for the clients without paths.
+    ids: SlotMap<Option<CablePath>>,
+    /// The clients with paths, ordered by the paths.
+    nodes: BTreeMap<CablePath, Node<T>>,
+    /// The clients without paths.
+    anonymous: BTreeMap<ConnectionId, T>,
+}
+
+/// A client with a path, and its id.
+type Node<T> = (ConnectionId, T);
+
+/// The range of the paths at or below the path.
+fn below(path: &CablePath) -> (Bound<CablePath>, Bound<CablePath>) {
+    let mut end = path.clone();
+    match end.segments.last_mut() {
+        // No segment is between `name` and `name\0`, so the paths below `name` are all before `name\0`
+        Some(last) => {
+            last.push('\0');
+            (Bound::Included(path.clone()), Bound::Excluded(end))
+        }
+        None => (Bound::Unbounded, Bound::Unbounded),
+    }
+}
+
+fn node<T>(node: &mut Node<T>) -> &mut T {
+    &mut node.1
+}
+
+fn node_with_id<T>(node: &mut Node<T>) -> (ConnectionId, &mut T) {
+    (node.0, &mut node.1)
+}
+
+fn path_node<'a, T>((_, x): (&'a CablePath, &'a mut Node<T>)) -> &'a mut T {
+    &mut x.1
+}
+
+fn path_node_with_id<'a, T>((_, x): (&'a CablePath, &'a mut Node<T>)) -> (ConnectionId, &'a mut T) {
+    (x.0, &mut x.1)
+}
+
+fn anonymous_with_id<'a, T>((id, x): (&'a ConnectionId, &'a mut T)) -> (ConnectionId, &'a mut T) {
+    (*id, x)
+}
+
+impl<T> Default for TreeCable<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> TreeCable<T> {
+    /// Create an empty TreeCable
+    pub fn new() -> Self {
+        Self {
+            ids: SlotMap::new(),
+            nodes: BTreeMap::new(),
+            anonymous: BTreeMap::new(),
+        }
+    }
+
+    /// Amount of the clients.
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    /// Check if there's no client.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Add a client at the path, which should neither be occupied nor below another client.
+    pub fn insert(
+        &mut self,
+        path: impl TryInto<CablePath, Error = PathError>,
+        client: T,
+    ) -> Result<ConnectionId, PathError> {
+        self.insert_at(path.try_into()?, client)
+    }
+
+    fn insert_at(&mut self, path: CablePath, client: T) -> Result<ConnectionId, PathError> {
+        if self.nodes.range(below(&path)).next().is_some() {
+            return Err(PathError::Occupied);
+        }
+        let mut parent = path.parent();
+        while let Some(node) = parent {
+            if self.nodes.contains_key(&node) {
+                return Err(PathError::UnderLeaf);
+            }
+            parent = node.parent();
+        }
+        let id = self.ids.insert(Some(path.clone()));
+        self.nodes.insert(path, (id, client));
+        Ok(id)
+    }
+
+    /// The path of a client, or `None` if it's absent or has no path.
+    pub fn path(&self, id: ConnectionId) -> Option<&CablePath> {
+        self.ids.get(id)?.as_ref()
+    }
+
+    /// The id of the client at the path.
+    pub fn id_of(&self, path: &CablePath) -> Option<ConnectionId> {
+        self.nodes.get(path).map(|(id, _)| *id)
+    }
+
+    /// Get the client at the path, to call it only.
+    pub fn get_by_path(&mut self, path: impl TryInto<CablePath, Error = PathError>) -> Option<&mut T> {
+        self.nodes.get_mut(&path.try_into().ok()?).map(node)
+    }
+
+    /// Get the clients below a node, to broadcast calls to them.
+    pub fn subtree(&mut self, path: impl TryInto<CablePath, Error = PathError>) -> Result<Subtree<'_, T>, PathError> {
+        Ok(Subtree {
+            tree: self,
+            path: path.try_into()?,
+        })
+    }
+
+    /// Pair the items of an attributed [`Bundle`] with the paths of the clients producing them.
+    pub fn resolve_paths<U, const N: usize>(&self, bundle: Bundle<U, N>) -> Vec<(Option<&CablePath>, U)> {
+        bundle
+            .into_attributed()
+            .map(|(id, x)| (id.and_then(|id| self.path(id)), x))
+            .collect()
+    }
+}
+
+impl<T: Connection> Connection for TreeCable<T> {
+    type Error = ChildErrors<T::Error>;
+
+    fn disconnect(&self) -> Result<(), Self::Error> {
+        let nodes = self.nodes.values().map(|(id, x)| (*id, x));
+        let anonymous = self.anonymous.iter().map(|(id, x)| (*id, x));
+        nodes
+            .chain(anonymous)
+            .filter_map(|(id, x)| x.disconnect().err().map(|e| (id, e)))
+            .collect::<ChildErrors<_>>()
+            .into_result()
+    }
+}
+
+impl<T> Cable for TreeCable<T> {
+    type AddError = Infallible;
+    type ChildIdIter<'a>
+        = Chain<
+        Map<ValuesMut<'a, CablePath, Node<T>>, fn(&'a mut Node<T>) -> (ConnectionId, &'a mut T)>,
+        Map<IterMut<'a, ConnectionId, T>, fn((&'a ConnectionId, &'a mut T)) -> (ConnectionId, &'a mut T)>,
+    >
+    where
+        T: 'a;
+    type ChildIter<'a>
+        =
+        Chain<Map<ValuesMut<'a, CablePath, Node<T>>, fn(&'a mut Node<T>) -> &'a mut T>, ValuesMut<'a, ConnectionId, T>>
+    where
+        T: 'a;
+    type Client = T;
+
+    /// Returns an iterator over the clients, those with paths in the order of the paths first.
+    fn iter_child(&mut self) -> Self::ChildIter<'_> {
+        let nodes = self.nodes.values_mut().map(node as fn(&mut Node<T>) -> &mut T);
+        nodes.chain(self.anonymous.values_mut())
+    }
+
+    fn iter_child_with_id(&mut self) -> Self::ChildIdIter<'_> {
+        let nodes = self
+            .nodes
+            .values_mut()
+            .map(node_with_id as fn(&mut Node<T>) -> (ConnectionId, &mut T));
+        let anonymous = self.anonymous.iter_mut().map(anonymous_with_id as fn(_) -> _);
+        nodes.chain(anonymous)
+    }
+
+    /// Add a client without any path.
+    fn add_connection(&mut self, addr: Self::Client) -> Result<ConnectionId, Self::AddError> {
+        let id = self.ids.insert(None);
+        self.anonymous.insert(id, addr);
+        Ok(id)
+    }
+
+    fn remove_connection(&mut self, id: ConnectionId) -> Option<Self::Client> {
+        match self.ids.remove(id)? {
+            Some(path) => self.nodes.remove(&path).map(|(_, x)| x),
+            None => self.anonymous.remove(&id),
+        }
+    }
+
+    fn get_mut(&mut self, id: ConnectionId) -> Option<&mut Self::Client> {
+        match self.ids.get(id)? {
+            Some(path) => self.nodes.get_mut(path).map(node),
+            None => self.anonymous.get_mut(&id),
+        }
+    }
+}
+
+
+
+/**
+The clients below a node of a [`TreeCable`], borrowed from it.
+
+It's a [`Cable`] of the clients below the node, with the same ids as in the tree,
+so [`dispatch_sub`](frincoe_macros::dispatch_sub) can be used on it to broadcast calls below the node,
+and the clients are found by walking the range of their paths rather than all the clients in the tree.
+Clients are added by [`Subtree::insert`] with paths relative to the node,
+since [`Cable::add_connection`] has no path for them and fails with [`PathError::NoPath`].
+Nodes below it are reached by [`Subtree::subtree`].
+*/
+#[derive(Debug)]
+pub struct Subtree<'a, T> {
+    tree: &'a mut TreeCable<T>,
+    path: CablePath,
+}
+
+impl<T> Subtree<'_, T> {
+    /// The path of the node.
+    pub fn path(&self) -> &CablePath {
+        &self.path
+    }
+
+    /// Amount of the clients.
+    pub fn len(&self) -> usize {
+        self.tree.nodes.range(below(&self.path)).count()
+    }
+
+    /// Check if there's no client.
+    pub fn is_empty(&self) -> bool {
+        self.tree.nodes.range(below(&self.path)).next().is_none()
+    }
+
+    /// Add a client at the path relative to the node, see [`TreeCable::insert`].
+    pub fn insert(
+        &mut self,
+        path: impl TryInto<CablePath, Error = PathError>,
+        client: T,
+    ) -> Result<ConnectionId, PathError> {
+        let path = self.path.join(&path.try_into()?);
+        self.tree.insert_at(path, client)
+    }
+
+    /// Get the clients below a node relative to this one.
+    pub fn subtree(&mut self, path: impl TryInto<CablePath, Error = PathError>) -> Result<Subtree<'_, T>, PathError> {
+        Ok(Subtree {
+            path: self.path.join(&path.try_into()?),
+            tree: self.tree,
+        })
+    }
+
+    /// Check if the client is below the node.
+    fn contains(&self, id: ConnectionId) -> bool {
+        self.tree.path(id).is_some_and(|x| x.starts_with(&self.path))
+    }
+}
+
+impl<T: Connection> Connection for Subtree<'_, T> {
+    type Error = ChildErrors<T::Error>;
+
+    fn disconnect(&self) -> Result<(), Self::Error> {
+        self.tree
+            .nodes
+            .range(below(&self.path))
+            .filter_map(|(_, (id, x))| x.disconnect().err().map(|e| (*id, e)))
+            .collect::<ChildErrors<_>>()
+            .into_result()
+    }
+}
+
+impl<'t, T> Cable for Subtree<'t, T> {
+    type AddError = PathError;
+    type ChildIdIter<'a>
+        = Map<RangeMut<'a, CablePath, Node<T>>, fn((&'a CablePath, &'a mut Node<T>)) -> (ConnectionId, &'a mut T)>
+    where
+        Self: 'a;
+    type ChildIter<'a>
+        = Map<RangeMut<'a, CablePath, Node<T>>, fn((&'a CablePath, &'a mut Node<T>)) -> &'a mut T>
+    where
+        Self: 'a;
+    type Client = T;
+
+    fn iter_child(&mut self) -> Self::ChildIter<'_> {
+        self.tree
+            .nodes
+            .range_mut(below(&self.path))
+            .map(path_node as fn(_) -> _)
+    }
+
+    fn iter_child_with_id(&mut self) -> Self::ChildIdIter<'_> {
+        self.tree
+            .nodes
+            .range_mut(below(&self.path))
+            .map(path_node_with_id as fn(_) -> _)
+    }
+
+    /// Fail with [`PathError::NoPath`], since clients below a node need paths, see [`Subtree::insert`].
+    fn add_connection(&mut self, _: Self::Client) -> Result<ConnectionId, Self::AddError> {
+        Err(PathError::NoPath)
+    }
+
+    fn remove_connection(&mut self, id: ConnectionId) -> Option<Self::Client> {
+        if !self.contains(id) {
+            return None;
+        }
+        self.tree.remove_connection(id)
+    }
+
+    fn get_mut(&mut self, id: ConnectionId) -> Option<&mut Self::Client> {
+        if !self.contains(id) {
+            return None;
+        }
+        self.tree.get_mut(id)
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use frincoe_rpc::Connection;
+
+    use super::{CablePath, PathError, TreeCable};
+    use crate::cable::Cable;
+
+    #[derive(Debug)]
+    struct Leaf;
+
+    impl Connection for Leaf {
+        type Error = ();
+
+        fn disconnect(&self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn paths() {
+        assert_eq!(CablePath::new("/"), Ok(CablePath::root()));
+        assert_eq!(CablePath::new("a/b"), Err(PathError::NotAbsolute));
+        assert_eq!(CablePath::new("/a//b"), Err(PathError::EmptySegment));
+        assert_eq!(CablePath::new("/a/b/"), Err(PathError::EmptySegment));
+        let path = CablePath::new("/a/b").unwrap();
+        assert_eq!(path.segments().collect::<Vec<_>>(), ["a", "b"]);
+        assert_eq!(path.to_string(), "/a/b");
+        assert_eq!(CablePath::root().to_string(), "/");
+        assert_eq!(path.parent().unwrap().to_string(), "/a");
+        assert!(path.starts_with(&CablePath::new("/a").unwrap()));
+        assert!(!path.starts_with(&CablePath::new("/a/bc").unwrap()));
+    }
+
+    #[test]
+    fn addressing() {
+        let mut tree = TreeCable::<Leaf>::new();
+        let a = tree.insert("/x/a", Leaf).unwrap();
+        tree.insert("/x/b/c", Leaf).unwrap();
+        tree.insert("/xy", Leaf).unwrap();
+        let anonymous = tree.add_connection(Leaf).unwrap();
+        assert_eq!(tree.insert("/x/a", Leaf).unwrap_err(), PathError::Occupied);
+        assert_eq!(tree.insert("/x/b", Leaf).unwrap_err(), PathError::Occupied);
+        assert_eq!(tree.insert("/x/a/d", Leaf).unwrap_err(), PathError::UnderLeaf);
+        assert_eq!(tree.len(), 4);
+        assert_eq!(tree.path(a).unwrap().to_string(), "/x/a");
+        assert_eq!(tree.path(anonymous), None);
+        // Siblings sharing a prefix of the name are not in the subtree
+        assert_eq!(tree.subtree("/x").unwrap().len(), 2);
+        assert_eq!(tree.subtree("/").unwrap().len(), 3);
+        assert_eq!(tree.subtree("/x/b/c").unwrap().len(), 1);
+        assert!(tree.subtree("/z").unwrap().is_empty());
+        // Removing a client frees its path
+        tree.remove_connection(a);
+        assert!(tree.get_by_path("/x/a").is_none());
+        tree.insert("/x/a/d", Leaf).unwrap();
+    }
+
+    #[test]
+    fn subtrees() {
+        let mut tree = TreeCable::<Leaf>::new();
+        let outside = tree.insert("/y", Leaf).unwrap();
+        let anonymous = tree.add_connection(Leaf).unwrap();
+        let mut x = tree.subtree("/x").unwrap();
+        assert_eq!(x.add_connection(Leaf), Err(PathError::NoPath));
+        let a = x.insert("/a", Leaf).unwrap();
+        let c = x.subtree("/b").unwrap().insert("/c", Leaf).unwrap();
+        assert_eq!(x.iter_child_with_id().map(|(id, _)| id).collect::<Vec<_>>(), [a, c]);
+        // Clients outside the node are not reached
+        assert!(x.get_mut(outside).is_none());
+        assert!(x.remove_connection(anonymous).is_none());
+        assert!(x.remove_connection(c).is_some());
+        assert_eq!(x.len(), 1);
+        assert_eq!(tree.path(a).unwrap().to_string(), "/x/a");
+        assert_eq!(tree.len(), 3);
+        // The clients with paths come first, in the order of their paths
+        let ids = tree.iter_child_with_id().map(|(id, _)| id).collect::<Vec<_>>();
+        assert_eq!(ids, [a, outside, anonymous]);
+    }
+}
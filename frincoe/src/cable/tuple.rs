@@ -0,0 +1,180 @@
+use std::ops::{Deref, DerefMut};
+
+use frincoe_rpc::Connection;
+
+use super::{ChildErrors, ConnectionId};
+
+
+
+/**
+Cable containing a fixed tuple of clients, possibly of different types.
+
+Since the clients are of different types, they can't be iterated,
+so this is not a [`Cable`](super::Cable);
+instead, the calls are forwarded to each of the clients statically,
+with the `fields` option of [`dispatch_sub`](frincoe_macros::dispatch_sub),
+without dynamic dispatching or borrowing the clients.
+The cable dereferences to the tuple, so the clients are accessed with `self.0`, `self.1`, etc.
+
+Disconnecting the cable disconnects all the clients in it,
+in which case the clients should have the same error type,
+and the ids in the errors are those returned by [`TupleCable::child_id`].
+
+```
+use frincoe::cable::{Bundle, TupleCable};
+use frincoe_macros::{dispatch_sub, inject_implement};
+
+trait Greet {
+    fn hello(&mut self, name: &str) -> Bundle<String>;
+}
+
+struct English;
+impl Greet for English {
+    fn hello(&mut self, name: &str) -> Bundle<String> {
+        Bundle::from_single(format!("hello {}", name))
+    }
+}
+
+struct Japanese;
+impl Greet for Japanese {
+    fn hello(&mut self, name: &str) -> Bundle<String> {
+        Bundle::from_single(format!("konnichiwa {}", name))
+    }
+}
+
+inject_implement! {
+    impl {
+        trait Greet {
+            fn hello(&mut self, name: &str) -> Bundle<String>;
+        }
+    } for TupleCable<(English, Japanese)> in dispatch_sub(fields(0, 1))
+}
+
+let mut cable = TupleCable::new((English, Japanese));
+assert_eq!(cable.hello("world"), ["hello world", "konnichiwa world"]);
+```
+*/
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq, Hash)]
+pub struct TupleCable<T> {
+    child: T,
+}
+
+impl<T> TupleCable<T> {
+    /// Create a cable from a tuple of clients.
+    pub fn new(child: T) -> Self {
+        Self { child }
+    }
+
+    /// Get the tuple of clients back.
+    pub fn into_inner(self) -> T {
+        self.child
+    }
+
+    /// The id for the client at the given position of the tuple, used in the errors.
+    pub fn child_id(index: u32) -> ConnectionId {
+        ConnectionId::positional(index)
+    }
+}
+
+impl<T> From<T> for TupleCable<T> {
+    fn from(child: T) -> Self {
+        Self::new(child)
+    }
+}
+
+impl<T> Deref for TupleCable<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.child
+    }
+}
+
+impl<T> DerefMut for TupleCable<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.child
+    }
+}
+
+macro_rules! impl_tuple_connection {
+    ( $( $name:ident : $index:tt ),+ ) => {
+        impl<E, $( $name: Connection<Error = E> ),+> Connection for TupleCable<($( $name, )+)> {
+            type Error = ChildErrors<E>;
+
+            fn disconnect(&self) -> Result<(), Self::Error> {
+                let mut errors = ChildErrors::new();
+                $(
+                    if let Err(e) = self.child.$index.disconnect() {
+                        errors.push(Self::child_id($index), e);
+                    }
+                )+
+                errors.into_result()
+            }
+        }
+    };
+}
+
+impl_tuple_connection!(A: 0);
+impl_tuple_connection!(A: 0, B: 1);
+impl_tuple_connection!(A: 0, B: 1, C: 2);
+impl_tuple_connection!(A: 0, B: 1, C: 2, D: 3);
+impl_tuple_connection!(A: 0, B: 1, C: 2, D: 3, F: 4);
+impl_tuple_connection!(A: 0, B: 1, C: 2, D: 3, F: 4, G: 5);
+impl_tuple_connection!(A: 0, B: 1, C: 2, D: 3, F: 4, G: 5, H: 6);
+impl_tuple_connection!(A: 0, B: 1, C: 2, D: 3, F: 4, G: 5, H: 6, I: 7);
+impl_tuple_connection!(A: 0, B: 1, C: 2, D: 3, F: 4, G: 5, H: 6, I: 7, J: 8);
+impl_tuple_connection!(A: 0, B: 1, C: 2, D: 3, F: 4, G: 5, H: 6, I: 7, J: 8, K: 9);
+impl_tuple_connection!(A: 0, B: 1, C: 2, D: 3, F: 4, G: 5, H: 6, I: 7, J: 8, K: 9, L: 10);
+impl_tuple_connection!(A: 0, B: 1, C: 2, D: 3, F: 4, G: 5, H: 6, I: 7, J: 8, K: 9, L: 10, M: 11);
+
+
+
+#[cfg(test)]
+mod tests {
+    use frincoe_rpc::Connection;
+
+    use super::TupleCable;
+    use crate::cable::ConnectionId;
+
+    /// A child failing to disconnect with its error, if any.
+    struct Child(Option<&'static str>);
+
+    impl Connection for Child {
+        type Error = &'static str;
+
+        fn disconnect(&self) -> Result<(), Self::Error> {
+            self.0.map_or(Ok(()), Err)
+        }
+    }
+
+    /// A client of another type, with the same error type.
+    struct Other;
+
+    impl Connection for Other {
+        type Error = &'static str;
+
+        fn disconnect(&self) -> Result<(), Self::Error> {
+            Err("other")
+        }
+    }
+
+    #[test]
+    fn disconnect() {
+        assert!(TupleCable::new((Child(None),)).disconnect().is_ok());
+        let cable = TupleCable::new((Child(Some("a")), Child(None), Other));
+        let errors = cable.disconnect().unwrap_err();
+        assert_eq!(
+            errors.into_iter().collect::<Vec<_>>(),
+            [
+                (ConnectionId::positional(0), "a"),
+                (ConnectionId::positional(2), "other")
+            ]
+        );
+        // The errors of nested cables cascade as a single error of the outer one
+        let nested = TupleCable::new((cable, TupleCable::new((Other,))));
+        let errors = nested.disconnect().unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors.get(TupleCable::<()>::child_id(0)).map(|x| x.len()), Some(2));
+        assert_eq!(errors.get(TupleCable::<()>::child_id(1)).map(|x| x.len()), Some(1));
+    }
+}
@@ -0,0 +1,464 @@
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::convert::Infallible;
+
+use frincoe_rpc::Connection;
+
+use super::slot_map::{SlotEntriesMut, SlotIterMut, SlotMap};
+use super::{Bundle, Cable, ChildErrors, ConnectionId};
+
+
+
+/// How many ballots a value needs to be decided by a [`VotingCable`], counted on its children.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Quorum {
+    /// Ballots from more than half of the children.
+    Majority,
+    /// At least the given amount of ballots.
+    AtLeast(usize),
+    /// Ballots from all of the children.
+    Unanimous,
+}
+
+/// How a [`VotingCable`] decides between values reaching the quorum with the same amount of ballots.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TieBreak {
+    /// Decide nothing, resulting in [`VoteOutcome::Tie`].
+    NoDecision,
+    /// Decide the value of the earliest ballot.
+    First,
+    /// Decide the value voted by the given child, or nothing if it's not one of the tied values.
+    Prefer(ConnectionId),
+}
+
+/// Result of a vote of a [`VotingCable`], with the ids of the children voting.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VoteOutcome<R> {
+    /// A value is decided.
+    Decided {
+        /// The value decided.
+        value: R,
+        /// Amount of the ballots for the value.
+        votes: usize,
+        /// The children voting for other values.
+        dissenters: Vec<Option<ConnectionId>>,
+        /// The children without any ballot.
+        abstainers: Vec<ConnectionId>,
+    },
+    /// No value reaches the quorum.
+    NoQuorum {
+        /// All the ballots.
+        ballots: Vec<(Option<ConnectionId>, R)>,
+        /// The children without any ballot.
+        abstainers: Vec<ConnectionId>,
+    },
+    /// Several values reach the quorum with the same amount of ballots, and the tie is not broken.
+    Tie {
+        /// All the ballots.
+        ballots: Vec<(Option<ConnectionId>, R)>,
+        /// The children without any ballot.
+        abstainers: Vec<ConnectionId>,
+    },
+}
+
+impl<R> VoteOutcome<R> {
+    /// Check if a value is decided.
+    pub fn is_decided(&self) -> bool {
+        matches!(self, Self::Decided { .. })
+    }
+
+    /// The value decided.
+    pub fn value(&self) -> Option<&R> {
+        match self {
+            Self::Decided { value, .. } => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Take the value decided.
+    pub fn into_value(self) -> Option<R> {
+        match self {
+            Self::Decided { value, .. } => Some(value),
+            _ => None,
+        }
+    }
+
+    /// The children voting for other values than the decided one, empty if nothing is decided.
+    pub fn dissenters(&self) -> &[Option<ConnectionId>] {
+        match self {
+            Self::Decided { dissenters, .. } => dissenters,
+            _ => &[],
+        }
+    }
+
+    /// The children without any ballot.
+    pub fn abstainers(&self) -> &[ConnectionId] {
+        match self {
+            Self::Decided { abstainers, .. } | Self::NoQuorum { abstainers, .. } | Self::Tie { abstainers, .. } => {
+                abstainers
+            }
+        }
+    }
+}
+
+
+
+/**
+Cable calling all of its children and voting on their results, for N-modular redundancy.
+
+The children are called with [`dispatch_sub`](frincoe_macros::dispatch_sub) as any other [`Cable`],
+preferably with the `attributed` option so that the children voting are recorded,
+and the resulting [`Bundle`] is decided by [`VotingCable::decide`] according to the [`Quorum`] and [`TieBreak`];
+or use [`VotingCable::vote`] to call a closure on every child and decide at once.
+The ballots are compared with `==`, so the results should be `Eq`.
+
+```
+use frincoe::cable::{Bundle, Cable, VoteOutcome, VotingCable};
+use frincoe_macros::{dispatch_sub, inject_implement};
+# use frincoe_rpc::Connection;
+
+trait Compute {
+    fn square(&mut self, x: i64) -> Bundle<i64>;
+}
+
+struct Unit(i64);
+impl Compute for Unit {
+    fn square(&mut self, x: i64) -> Bundle<i64> {
+        Bundle::from_single(x * x + self.0)
+    }
+}
+# impl Connection for Unit {
+#     type Error = ();
+#     fn disconnect(&self) -> Result<(), ()> { Ok(()) }
+# }
+
+inject_implement! {
+    impl {
+        trait Compute {
+            fn square(&mut self, x: i64) -> Bundle<i64>;
+        }
+    } for VotingCable<Unit> in dispatch_sub(attributed)
+}
+
+let mut cable = VotingCable::new();
+cable.add_connection(Unit(0)).unwrap();
+let faulty = cable.add_connection(Unit(1)).unwrap();
+cable.add_connection(Unit(0)).unwrap();
+let ballots = cable.square(3);
+match cable.decide(ballots) {
+    VoteOutcome::Decided { value, votes, dissenters, .. } => {
+        assert_eq!((value, votes), (9, 2));
+        assert_eq!(dissenters, [Some(faulty)]);
+    }
+    _ => unreachable!(),
+}
+```
+*/
+#[derive(Clone, Debug)]
+pub struct VotingCable<T> {
+    child: SlotMap<T>,
+    quorum: Quorum,
+    tie_break: TieBreak,
+}
+
+impl<T> Default for VotingCable<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> VotingCable<T> {
+    /// Create an empty VotingCable, deciding by majority without breaking ties.
+    pub fn new() -> Self {
+        Self::with_rules(Quorum::Majority, TieBreak::NoDecision)
+    }
+
+    /// Create an empty VotingCable with the rules to decide.
+    pub fn with_rules(quorum: Quorum, tie_break: TieBreak) -> Self {
+        Self {
+            child: SlotMap::new(),
+            quorum,
+            tie_break,
+        }
+    }
+
+    /// Amount of the clients.
+    pub fn len(&self) -> usize {
+        self.child.len()
+    }
+
+    /// Check if there's no client.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The quorum to decide a value.
+    pub fn quorum(&self) -> Quorum {
+        self.quorum
+    }
+
+    /// The rule to break ties.
+    pub fn tie_break(&self) -> TieBreak {
+        self.tie_break
+    }
+
+    /**
+    Decide a value from the ballots, usually the result of an attributed call.
+
+    The quorum is counted on the children rather than the ballots,
+    so children returning nothing abstain, which counts against a value as a dissent does.
+    They're reported as the abstainers if the ballots are attributed;
+    with unattributed ballots, the children abstaining can't be told, so none is reported.
+
+    A child returning several attributed ballots votes once, for their value if they're all equal,
+    and dissents from any value otherwise.
+    Unattributed ballots can't be told apart by child, so each of them is counted as a vote,
+    and a child returning several of them votes several times.
+    */
+    pub fn decide<R: Eq, const N: usize>(&self, ballots: Bundle<R, N>) -> VoteOutcome<R> {
+        let mut ballots = ballots.into_attributed().collect::<Vec<_>>();
+        let abstainers = if ballots.iter().all(|(id, _)| id.is_some()) {
+            let voted = ballots.iter().filter_map(|(id, _)| *id).collect::<HashSet<_>>();
+            self.child
+                .iter()
+                .map(|(id, _)| id)
+                .filter(|id| !voted.contains(id))
+                .collect()
+        } else {
+            vec![]
+        };
+        // Each child votes once with its first ballot, unless its ballots disagree
+        let mut first = HashMap::new();
+        let mut split = HashSet::new();
+        let mut voting = vec![];
+        for (i, (id, value)) in ballots.iter().enumerate() {
+            match id.map(|id| (id, first.entry(id))) {
+                None => voting.push(i),
+                Some((_, Entry::Vacant(entry))) => {
+                    entry.insert(i);
+                    voting.push(i);
+                }
+                Some((id, Entry::Occupied(entry))) => {
+                    if ballots[*entry.get()].1 != *value {
+                        split.insert(id);
+                    }
+                }
+            }
+        }
+        voting.retain(|i| ballots[*i].0.is_none_or(|id| !split.contains(&id)));
+        // Group the votes by their values, in the order of the first votes of the values
+        let mut groups: Vec<Vec<usize>> = vec![];
+        for (i, value) in voting.into_iter().map(|i| (i, &ballots[i].1)) {
+            match groups.iter_mut().find(|x| ballots[x[0]].1 == *value) {
+                Some(group) => group.push(i),
+                None => groups.push(vec![i]),
+            }
+        }
+        let required = match self.quorum {
+            Quorum::Majority => self.len() / 2 + 1,
+            Quorum::AtLeast(n) => n,
+            Quorum::Unanimous => self.len(),
+        }
+        .max(1);
+        let votes = groups.iter().map(Vec::len).max().unwrap_or(0);
+        if votes < required {
+            return VoteOutcome::NoQuorum { ballots, abstainers };
+        }
+        let mut tied = groups.iter().filter(|x| x.len() == votes);
+        let winner = match (self.tie_break, groups.iter().filter(|x| x.len() == votes).count()) {
+            (_, 1) | (TieBreak::First, _) => tied.next(),
+            (TieBreak::Prefer(id), _) => tied.find(|x| x.iter().any(|i| ballots[*i].0 == Some(id))),
+            (TieBreak::NoDecision, _) => None,
+        };
+        let winner = match winner {
+            Some(v) => v[0],
+            None => return VoteOutcome::Tie { ballots, abstainers },
+        };
+        let value = &ballots[winner].1;
+        let mut counted = HashSet::new();
+        let dissenters = ballots
+            .iter()
+            .filter(|(id, x)| match id {
+                None => x != value,
+                Some(id) => counted.insert(*id) && (split.contains(id) || ballots[first[id]].1 != *value),
+            })
+            .map(|(id, _)| *id)
+            .collect();
+        VoteOutcome::Decided {
+            value: ballots.swap_remove(winner).1,
+            votes,
+            dissenters,
+            abstainers,
+        }
+    }
+
+    /// Call every child with the closure, and decide a value from the results.
+    pub fn vote<R: Eq>(&mut self, mut f: impl FnMut(&mut T) -> R) -> VoteOutcome<R> {
+        let mut ballots = Bundle::<R, 4>::new();
+        for (id, it) in self.child.entries_mut() {
+            ballots.extend_from(id, Bundle::from_single(f(it)));
+        }
+        self.decide(ballots)
+    }
+}
+
+impl<T: Connection> Connection for VotingCable<T> {
+    type Error = ChildErrors<T::Error>;
+
+    fn disconnect(&self) -> Result<(), Self::Error> {
+        self.child
+            .iter()
+            .filter_map(|(id, x)| x.disconnect().err().map(|e| (id, e)))
+            .collect::<ChildErrors<_>>()
+            .into_result()
+    }
+}
+
+impl<T> Cable for VotingCable<T> {
+    type AddError = Infallible;
+    type ChildIdIter<'a>
+        = SlotEntriesMut<'a, T>
+    where
+        T: 'a;
+    type ChildIter<'a>
+        = SlotIterMut<'a, T>
+    where
+        T: 'a;
+    type Client = T;
+
+    fn iter_child(&mut self) -> Self::ChildIter<'_> {
+        self.child.iter_mut()
+    }
+
+    fn iter_child_with_id(&mut self) -> Self::ChildIdIter<'_> {
+        self.child.entries_mut()
+    }
+
+    fn add_connection(&mut self, addr: Self::Client) -> Result<ConnectionId, Self::AddError> {
+        Ok(self.child.insert(addr))
+    }
+
+    fn remove_connection(&mut self, id: ConnectionId) -> Option<Self::Client> {
+        self.child.remove(id)
+    }
+
+    fn get_mut(&mut self, id: ConnectionId) -> Option<&mut Self::Client> {
+        self.child.get_mut(id)
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::{Quorum, TieBreak, VoteOutcome, VotingCable};
+    use crate::cable::{Bundle, Cable, ConnectionId};
+
+    /// A cable of `n` children, whose ids are the positional ones.
+    fn cable(n: u32, quorum: Quorum, tie_break: TieBreak) -> VotingCable<()> {
+        let mut cable = VotingCable::with_rules(quorum, tie_break);
+        for _ in 0..n {
+            cable.add_connection(()).unwrap();
+        }
+        cable
+    }
+
+    fn ballots(values: &[i32]) -> Bundle<i32, 4> {
+        let mut res = Bundle::new();
+        for (i, x) in values.iter().enumerate() {
+            res.extend_from(ConnectionId::positional(i as u32), Bundle::from_single(*x));
+        }
+        res
+    }
+
+    #[test]
+    fn quorum() {
+        let majority = cable(3, Quorum::Majority, TieBreak::NoDecision);
+        assert_eq!(majority.decide(ballots(&[1, 1, 2])).value(), Some(&1));
+        assert_eq!(
+            majority.decide(ballots(&[1, 1, 2])).dissenters(),
+            [Some(ConnectionId::positional(2))]
+        );
+        assert!(matches!(
+            majority.decide(ballots(&[1, 2, 3])),
+            VoteOutcome::NoQuorum { .. }
+        ));
+        assert!(matches!(
+            VotingCable::<()>::new().decide(ballots(&[])),
+            VoteOutcome::NoQuorum { .. }
+        ));
+        let unanimous = cable(3, Quorum::Unanimous, TieBreak::NoDecision);
+        assert!(!unanimous.decide(ballots(&[1, 1, 2])).is_decided());
+        assert_eq!(unanimous.decide(ballots(&[2, 2, 2])).into_value(), Some(2));
+    }
+
+    #[test]
+    fn abstain() {
+        let id = ConnectionId::positional;
+        // A single ballot out of 3 children is not a majority
+        let majority = cable(3, Quorum::Majority, TieBreak::NoDecision);
+        let outcome = majority.decide(ballots(&[1]));
+        assert!(matches!(outcome, VoteOutcome::NoQuorum { .. }));
+        assert_eq!(outcome.abstainers(), [id(1), id(2)]);
+        let outcome = majority.decide(ballots(&[1, 1]));
+        assert_eq!(outcome.value(), Some(&1));
+        assert_eq!(outcome.abstainers(), [id(2)]);
+        // Abstaining breaks unanimity
+        let unanimous = cable(3, Quorum::Unanimous, TieBreak::NoDecision);
+        assert!(!unanimous.decide(ballots(&[2, 2])).is_decided());
+        // The children abstaining can't be told without attribution
+        let outcome = majority.decide(Bundle::<i32>::from_iter([1, 1]));
+        assert!(outcome.is_decided());
+        assert!(outcome.abstainers().is_empty());
+    }
+
+    #[test]
+    fn one_vote_per_child() {
+        let id = ConnectionId::positional;
+        let majority = cable(3, Quorum::Majority, TieBreak::NoDecision);
+        // Two equal ballots of a single child are a single vote
+        let mut twice = Bundle::<i32, 4>::new();
+        twice.extend_from(id(0), Bundle::from_iter([7, 7]));
+        let outcome = majority.decide(twice.clone());
+        assert!(matches!(outcome, VoteOutcome::NoQuorum { .. }));
+        assert_eq!(outcome.abstainers(), [id(1), id(2)]);
+        twice.extend_from(id(1), Bundle::from_single(7));
+        let outcome = majority.decide(twice);
+        assert_eq!(outcome.value(), Some(&7));
+        assert!(matches!(outcome, VoteOutcome::Decided { votes: 2, .. }));
+        // A child whose ballots disagree dissents from any value
+        let mut split = ballots(&[]);
+        split.extend_from(id(0), Bundle::from_iter([7, 8]));
+        split.extend_from(id(1), Bundle::from_single(8));
+        split.extend_from(id(2), Bundle::from_single(8));
+        let outcome = majority.decide(split);
+        assert_eq!(outcome.value(), Some(&8));
+        assert_eq!(outcome.dissenters(), [Some(id(0))]);
+        assert!(!cable(3, Quorum::Unanimous, TieBreak::NoDecision)
+            .decide({
+                let mut split = ballots(&[8, 8]);
+                split.extend_from(id(2), Bundle::from_iter([8, 9]));
+                split
+            })
+            .is_decided());
+    }
+
+    #[test]
+    fn tie_break() {
+        let rules = |tie_break| cable(5, Quorum::AtLeast(2), tie_break);
+        let tied = [1, 2, 2, 1, 3];
+        assert!(matches!(
+            rules(TieBreak::NoDecision).decide(ballots(&tied)),
+            VoteOutcome::Tie { .. }
+        ));
+        assert_eq!(rules(TieBreak::First).decide(ballots(&tied)).value(), Some(&1));
+        let prefer = |i| TieBreak::Prefer(ConnectionId::positional(i));
+        assert_eq!(rules(prefer(1)).decide(ballots(&tied)).value(), Some(&2));
+        assert!(!rules(prefer(4)).decide(ballots(&tied)).is_decided());
+        // Ties are not broken below the quorum
+        assert!(matches!(
+            rules(TieBreak::First).decide(ballots(&[1, 2])),
+            VoteOutcome::NoQuorum { .. }
+        ));
+    }
+}
@@ -0,0 +1,183 @@
+use std::sync::{Arc, Mutex, MutexGuard, Weak};
+use std::vec;
+
+use frincoe_rpc::Connection;
+
+use super::slot_map::SlotMap;
+use super::{ChildErrors, ConnectionId};
+
+
+
+/**
+Cable holding weak references to its clients, so that it doesn't keep them alive.
+
+The clients are owned elsewhere in [`Arc`]s, and are removed from the cable once their owners drop them;
+the removal happens when iterating over the clients, or explicitly with [`WeakCable::prune`].
+The clients are kept behind a lock, so they can be added or removed through `&self`, even by the clients being called.
+So unlike a cable of `&mut T`, the clients are not borrowed by the cable,
+and unlike a cable of `Arc<T>`, dropped clients are not called anymore.
+
+Since the clients are upgraded into [`Arc`]s when iterated, they're called through `&self`,
+so this is not a [`Cable`](super::Cable);
+it provides `iter_child` and `iter_child_with_id`,
+so that [`dispatch_sub`](frincoe_macros::dispatch_sub) can still be used on it for methods taking `&self`.
+
+```
+use std::sync::Arc;
+use frincoe::cable::{Bundle, WeakCable};
+use frincoe_macros::{dispatch_sub, inject_implement};
+
+trait Notify {
+    fn changed(&self, field: &str) -> Bundle<String>;
+}
+
+struct Label(&'static str);
+impl Notify for Label {
+    fn changed(&self, field: &str) -> Bundle<String> {
+        Bundle::from_single(format!("{} sees {}", self.0, field))
+    }
+}
+
+inject_implement! {
+    impl {
+        trait Notify {
+            fn changed(&self, field: &str) -> Bundle<String>;
+        }
+    } for WeakCable<dyn Notify> in dispatch_sub
+}
+
+let title = Arc::new(Label("title"));
+let status = Arc::new(Label("status"));
+let cable = WeakCable::<dyn Notify>::new();
+cable.add_connection(Arc::downgrade(&title) as _);
+cable.add_connection(Arc::downgrade(&status) as _);
+assert_eq!(cable.changed("name"), ["title sees name", "status sees name"]);
+// The dropped client is removed
+drop(title);
+assert_eq!(cable.changed("name"), ["status sees name"]);
+assert_eq!(cable.len(), 1);
+```
+*/
+#[derive(Debug)]
+pub struct WeakCable<T: ?Sized> {
+    child: Mutex<SlotMap<Weak<T>>>,
+}
+
+impl<T: ?Sized> Default for WeakCable<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: ?Sized> WeakCable<T> {
+    /// Create an empty WeakCable
+    pub fn new() -> Self {
+        Self {
+            child: Mutex::new(SlotMap::new()),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, SlotMap<Weak<T>>> {
+        self.child.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Amount of the clients, including the dropped ones not removed yet.
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Check if there's no client, including the dropped ones not removed yet.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Add a client, returning its id.
+    pub fn add_connection(&self, addr: Weak<T>) -> ConnectionId {
+        self.lock().insert(addr)
+    }
+
+    /// Remove a client, returning it if it's present.
+    pub fn remove_connection(&self, id: ConnectionId) -> Option<Weak<T>> {
+        self.lock().remove(id)
+    }
+
+    /// Get a client by its id, if it's still alive.
+    pub fn get(&self, id: ConnectionId) -> Option<Arc<T>> {
+        self.lock().get_mut(id)?.upgrade()
+    }
+
+    /// Remove the dropped clients, returning how many are removed.
+    pub fn prune(&self) -> usize {
+        self.lock().retain(|_, x| x.strong_count() > 0)
+    }
+
+    /// Returns an iterator over the alive clients, removing the dropped ones.
+    pub fn iter_child(&self) -> impl Iterator<Item = Arc<T>> {
+        self.iter_child_with_id().map(|(_, x)| x)
+    }
+
+    /**
+    Returns an iterator over the alive clients and their ids, removing the dropped ones.
+
+    The clients are collected before the iteration,
+    so clients can be added or removed during the iteration, e.g. by the clients called,
+    which doesn't affect the iteration.
+    */
+    pub fn iter_child_with_id(&self) -> vec::IntoIter<(ConnectionId, Arc<T>)> {
+        let mut alive = vec![];
+        self.lock().retain(|id, x| match x.upgrade() {
+            Some(client) => {
+                alive.push((id, client));
+                true
+            }
+            None => false,
+        });
+        alive.into_iter()
+    }
+}
+
+impl<T: Connection + ?Sized> Connection for WeakCable<T> {
+    type Error = ChildErrors<T::Error>;
+
+    /// Disconnect the alive clients.
+    fn disconnect(&self) -> Result<(), Self::Error> {
+        self.iter_child_with_id()
+            .filter_map(|(id, x)| x.disconnect().err().map(|e| (id, e)))
+            .collect::<ChildErrors<_>>()
+            .into_result()
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::WeakCable;
+
+    #[test]
+    fn pruning() {
+        let a = Arc::new(1);
+        let b = Arc::new(2);
+        let c = Arc::new(3);
+        let cable = WeakCable::new();
+        let a_id = cable.add_connection(Arc::downgrade(&a));
+        let b_id = cable.add_connection(Arc::downgrade(&b));
+        cable.add_connection(Arc::downgrade(&c));
+        drop(b);
+        // Dropped clients are kept until pruned
+        assert_eq!(cable.len(), 3);
+        assert!(cable.get(b_id).is_none());
+        assert_eq!(cable.iter_child().map(|x| *x).collect::<Vec<_>>(), [1, 3]);
+        assert_eq!(cable.len(), 2);
+        drop(c);
+        assert_eq!(cable.prune(), 1);
+        assert_eq!(cable.prune(), 0);
+        assert_eq!(cable.get(a_id).as_deref(), Some(&1));
+        // The cable doesn't keep the clients alive
+        drop(a);
+        assert_eq!(cable.iter_child().count(), 0);
+        assert!(cable.is_empty());
+    }
+}
@@ -1,3 +1,9 @@
+use std::collections::HashMap;
+
+use frincoe_rpc::{CallContext, Peer};
+
+
+
 /**
 Unauthorized text with a count and a name, used for tests.
 
@@ -26,11 +32,28 @@ pub trait CountedText {
 Provide an implement to [`CountedText`].
 
 For detailed document, see [`CountedText`].
+Created by [`CountedTextProvider::per_caller`], it keeps a separate count for each [`Peer`] calling,
+read from the [`CallContext`], and the anonymous callers share one count.
+
+```
+# use frincoe::interfaces;
+use frincoe_rpc::{CallContext, Peer};
+use interfaces::{CountedText, CountedTextProvider};
+
+let alice = CallContext::default().with_peer(Some(Peer::Principal("alice".to_string())));
+let bob = CallContext::default().with_peer(Some(Peer::Principal("bob".to_string())));
+let mut msg = CountedTextProvider::per_caller("fc");
+assert_eq!(alice.scope(|| msg.send("hello")), "recv(fc) 1 5: hello\n");
+assert_eq!(bob.scope(|| msg.send("hi")), "recv(fc) 1 2: hi\n");
+assert_eq!(alice.scope(|| msg.send("bye")), "recv(fc) 2 3: bye\n");
+assert_eq!(msg.send("anonymous"), "recv(fc) 1 9: anonymous\n");
+```
 */
 #[derive(Debug, Clone)]
 pub struct CountedTextProvider {
     name: String,
     id: u32,
+    callers: Option<HashMap<Option<Peer>, u32>>,
 }
 
 impl CountedTextProvider {
@@ -39,14 +62,27 @@ impl CountedTextProvider {
         Self {
             name: name.to_string(),
             id: 0,
+            callers: None,
+        }
+    }
+
+    /// Create an object counting from 0 for each caller
+    pub fn per_caller(name: impl ToString) -> Self {
+        Self {
+            callers: Some(HashMap::new()),
+            ..Self::new(name)
         }
     }
 }
 
 impl CountedText for CountedTextProvider {
     fn send(&mut self, text: impl ToString) -> String {
-        self.id += 1;
+        let id = match &mut self.callers {
+            Some(callers) => callers.entry(CallContext::current().peer().cloned()).or_default(),
+            None => &mut self.id,
+        };
+        *id += 1;
         let text = text.to_string();
-        format!("recv({}) {} {}: {}\n", self.name, self.id, text.len(), text)
+        format!("recv({}) {} {}: {}\n", self.name, id, text.len(), text)
     }
 }
@@ -11,6 +11,8 @@ pub mod cable;
 
 pub mod interfaces;
 
+pub mod topic;
+
 
 
 #[cfg(test)]
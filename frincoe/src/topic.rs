@@ -0,0 +1,440 @@
+/*!
+Hierarchical topics with MQTT-style wildcards.
+
+A topic is a list of levels separated by `/`, e.g. `sensors/kitchen/temp`.
+A topic filter is a topic whose levels may be wildcards:
+`+` matches exactly one level, and `#`, which must be the last level,
+matches the parent level and any number of levels below it.
+So `sensors/+/temp` matches `sensors/kitchen/temp` but not `sensors/kitchen/fan/temp`,
+and `logs/#` matches `logs`, `logs/app` and `logs/app/error`.
+Wildcards must occupy a whole level, so `sensor+/temp` and `logs#` are invalid filters.
+
+As in MQTT, topics whose first level starts with `$` (e.g. `$SYS/uptime`)
+are not matched by a wildcard at the first level of a filter.
+
+[`TopicTree`] keeps subscribers in a trie indexed by the levels of their filters,
+so subscribing and unsubscribing cost `O(depth)`,
+and publishing only visits the branches and subscribers matching the topic.
+
+# Example
+
+```
+use frincoe::cable::ArrayCable;
+use frincoe::topic::TopicTree;
+
+let mut tree = TopicTree::new();
+let kitchen = tree.subscribe("sensors/kitchen/temp", "kitchen").unwrap();
+tree.subscribe("sensors/+/temp", "any temp").unwrap();
+tree.subscribe("sensors/#", "any sensor").unwrap();
+
+let names = |cable: ArrayCable<&mut &str>| cable.into_iter().map(|x| x.to_string()).collect::<Vec<_>>();
+let routed = tree.route("sensors/kitchen/temp").unwrap();
+assert_eq!(names(routed), ["kitchen", "any temp", "any sensor"]);
+let routed = tree.route("sensors/hall/humidity").unwrap();
+assert_eq!(names(routed), ["any sensor"]);
+
+assert_eq!(tree.unsubscribe(kitchen), Some("kitchen"));
+let routed = tree.route("sensors/kitchen/temp").unwrap();
+assert_eq!(names(routed), ["any temp", "any sensor"]);
+```
+*/
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{self, Display};
+use std::str::{FromStr, Split};
+
+use crate::cable::ArrayCable;
+
+
+
+/// Errors when parsing topics and topic filters.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TopicError {
+    /// The topic or filter is an empty string.
+    Empty,
+    /// A topic to publish contains wildcards, which are only allowed in filters.
+    WildcardInTopic,
+    /// A wildcard doesn't occupy a whole level, or `#` is not the last level.
+    MisplacedWildcard,
+}
+
+impl Display for TopicError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Empty => "the topic is empty",
+            Self::WildcardInTopic => "wildcards are not allowed in a topic to publish",
+            Self::MisplacedWildcard => "a wildcard should occupy a whole level, and `#` should be the last level",
+        })
+    }
+}
+
+impl std::error::Error for TopicError {}
+
+/// Check if a topic to publish is valid, i.e. non-empty and without wildcards.
+pub fn validate_topic(topic: &str) -> Result<(), TopicError> {
+    if topic.is_empty() {
+        Err(TopicError::Empty)
+    } else if topic.contains(['+', '#']) {
+        Err(TopicError::WildcardInTopic)
+    } else {
+        Ok(())
+    }
+}
+
+
+
+/**
+A validated topic filter, possibly with wildcards.
+
+For the syntax of filters, see the [module-level document](self).
+*/
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TopicFilter {
+    filter: String,
+}
+
+impl TopicFilter {
+    /// Parse and validate a filter.
+    pub fn new(filter: impl Into<String>) -> Result<Self, TopicError> {
+        let filter = filter.into();
+        if filter.is_empty() {
+            return Err(TopicError::Empty);
+        }
+        let mut levels = filter.split('/').peekable();
+        while let Some(level) = levels.next() {
+            let misplaced = match level {
+                "+" => false,
+                "#" => levels.peek().is_some(),
+                _ => level.contains(['+', '#']),
+            };
+            if misplaced {
+                return Err(TopicError::MisplacedWildcard);
+            }
+        }
+        Ok(Self { filter })
+    }
+
+    /// The filter as a string.
+    pub fn as_str(&self) -> &str {
+        &self.filter
+    }
+
+    /// Iterate over the levels of the filter.
+    pub fn levels(&self) -> Split<'_, char> {
+        self.filter.split('/')
+    }
+
+    /// Check if the filter matches a topic.
+    ///
+    /// The topic is not validated; to match a lot of filters at once, use [`TopicTree`].
+    pub fn matches(&self, topic: &str) -> bool {
+        if topic.starts_with('$') && matches!(self.filter.as_bytes()[0], b'+' | b'#') {
+            return false;
+        }
+        let mut filter = self.levels();
+        let mut topic = topic.split('/');
+        loop {
+            match (filter.next(), topic.next()) {
+                (Some("#"), _) => return true,
+                (Some("+"), Some(_)) => (),
+                (Some(lhs), Some(rhs)) if lhs == rhs => (),
+                (None, None) => return true,
+                _ => return false,
+            }
+        }
+    }
+}
+
+impl FromStr for TopicFilter {
+    type Err = TopicError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::new(s)
+    }
+}
+
+impl TryFrom<&str> for TopicFilter {
+    type Error = TopicError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        Self::new(value)
+    }
+}
+
+impl TryFrom<String> for TopicFilter {
+    type Error = TopicError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::new(value)
+    }
+}
+
+impl Display for TopicFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.filter)
+    }
+}
+
+
+
+/// The handle of a subscription in a [`TopicTree`], used to unsubscribe.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SubscriptionId(u64);
+
+/// A node of the trie, i.e. a level of filters.
+#[derive(Debug)]
+struct Node<T> {
+    /// Children by exact level names.
+    children: HashMap<String, Node<T>>,
+    /// Child for the `+` wildcard.
+    single: Option<Box<Node<T>>>,
+    /// Subscribers with a `#` wildcard in the next level.
+    multi: BTreeMap<SubscriptionId, T>,
+    /// Subscribers whose filter ends at this level.
+    exact: BTreeMap<SubscriptionId, T>,
+}
+
+impl<T> Default for Node<T> {
+    fn default() -> Self {
+        Self {
+            children: HashMap::new(),
+            single: None,
+            multi: BTreeMap::new(),
+            exact: BTreeMap::new(),
+        }
+    }
+}
+
+impl<T> Node<T> {
+    fn is_empty(&self) -> bool {
+        self.children.is_empty() && self.single.is_none() && self.multi.is_empty() && self.exact.is_empty()
+    }
+
+    /// Collect subscribers matching the rest levels of a topic;
+    /// `dollar` is whether wildcards should be skipped for a `$` topic.
+    fn collect<'a>(&'a mut self, levels: &[&str], dollar: bool, out: &mut Vec<(SubscriptionId, &'a mut T)>) {
+        let Self {
+            children,
+            single,
+            multi,
+            exact,
+        } = self;
+        if !dollar {
+            out.extend(multi.iter_mut().map(|(id, x)| (*id, x)));
+        }
+        match levels.split_first() {
+            None => out.extend(exact.iter_mut().map(|(id, x)| (*id, x))),
+            Some((head, rest)) => {
+                if let Some(child) = children.get_mut(*head) {
+                    child.collect(rest, false, out);
+                }
+                if let (Some(child), false) = (single, dollar) {
+                    child.collect(rest, false, out);
+                }
+            }
+        }
+    }
+
+    /// Remove a subscriber with the rest levels of its filter, pruning empty nodes.
+    fn remove(&mut self, levels: &[&str], id: SubscriptionId) -> Option<T> {
+        match levels.split_first() {
+            None => self.exact.remove(&id),
+            Some((&"#", _)) => self.multi.remove(&id),
+            Some((&"+", rest)) => {
+                let child = self.single.as_mut()?;
+                let res = child.remove(rest, id);
+                if child.is_empty() {
+                    self.single = None;
+                }
+                res
+            }
+            Some((head, rest)) => {
+                let child = self.children.get_mut(*head)?;
+                let res = child.remove(rest, id);
+                if child.is_empty() {
+                    self.children.remove(*head);
+                }
+                res
+            }
+        }
+    }
+}
+
+
+
+/**
+Subscribers indexed by topic filters, in a trie.
+
+Subscribing and unsubscribing cost `O(depth)` of the filter,
+and routing a topic only visits the subscribers matching it,
+as well as the trie nodes on the way to them.
+Matched subscribers are always in the order of subscription.
+
+For the syntax of topics and filters, see the [module-level document](self).
+*/
+#[derive(Debug)]
+pub struct TopicTree<T> {
+    root: Node<T>,
+    filters: HashMap<SubscriptionId, TopicFilter>,
+    next_id: u64,
+}
+
+impl<T> Default for TopicTree<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> TopicTree<T> {
+    /// Create a tree without subscribers.
+    pub fn new() -> Self {
+        Self {
+            root: Node::default(),
+            filters: HashMap::new(),
+            next_id: 0,
+        }
+    }
+
+    /// Amount of the subscribers.
+    pub fn len(&self) -> usize {
+        self.filters.len()
+    }
+
+    /// Check if there's no subscriber.
+    pub fn is_empty(&self) -> bool {
+        self.filters.is_empty()
+    }
+
+    /// Get the filter of a subscription.
+    pub fn filter(&self, id: SubscriptionId) -> Option<&TopicFilter> {
+        self.filters.get(&id)
+    }
+
+    /// Subscribe to topics matching the filter.
+    ///
+    /// If the filter is invalid, the subscriber is not added.
+    pub fn subscribe<F>(&mut self, filter: F, subscriber: T) -> Result<SubscriptionId, TopicError>
+    where
+        F: TryInto<TopicFilter, Error = TopicError>,
+    {
+        let filter = filter.try_into()?;
+        let id = SubscriptionId(self.next_id);
+        self.next_id += 1;
+        let mut node = &mut self.root;
+        let mut levels = filter.levels();
+        loop {
+            match levels.next() {
+                None => {
+                    node.exact.insert(id, subscriber);
+                    break;
+                }
+                Some("#") => {
+                    node.multi.insert(id, subscriber);
+                    break;
+                }
+                Some("+") => node = node.single.get_or_insert_with(Default::default),
+                Some(level) => node = node.children.entry(level.to_string()).or_default(),
+            }
+        }
+        self.filters.insert(id, filter);
+        Ok(id)
+    }
+
+    /// Remove a subscriber, returning it if it's still subscribed.
+    pub fn unsubscribe(&mut self, id: SubscriptionId) -> Option<T> {
+        let filter = self.filters.remove(&id)?;
+        self.root.remove(&filter.levels().collect::<Vec<_>>(), id)
+    }
+
+    /// Collect the subscribers matching the topic into a cable, for publishing to them.
+    pub fn route(&mut self, topic: &str) -> Result<ArrayCable<&mut T>, TopicError> {
+        validate_topic(topic)?;
+        let levels = topic.split('/').collect::<Vec<_>>();
+        let mut matched = vec![];
+        self.root.collect(&levels, topic.starts_with('$'), &mut matched);
+        matched.sort_unstable_by_key(|(id, _)| *id);
+        Ok(matched.into_iter().map(|(_, x)| x).collect())
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::{TopicError, TopicFilter, TopicTree};
+
+    fn route(tree: &mut TopicTree<i32>, topic: &str) -> Vec<i32> {
+        tree.route(topic).unwrap().into_iter().map(|x| *x).collect()
+    }
+
+    #[test]
+    fn parse_filter() {
+        for filter in ["a", "a/b", "+", "#", "a/+/b", "a/#", "+/+/#", "/", "a//b", "$SYS/#"] {
+            assert!(TopicFilter::new(filter).is_ok(), "{}", filter);
+        }
+        assert_eq!(TopicFilter::new(""), Err(TopicError::Empty));
+        for filter in ["a+", "a/#/b", "#/a", "a/b#", "++", "##"] {
+            assert_eq!(
+                TopicFilter::new(filter),
+                Err(TopicError::MisplacedWildcard),
+                "{}",
+                filter
+            );
+        }
+    }
+
+    #[test]
+    fn match_filter() {
+        let filter = |x| TopicFilter::new(x).unwrap();
+        assert!(filter("a/+/c").matches("a/b/c"));
+        assert!(filter("a/+/c").matches("a//c"));
+        assert!(!filter("a/+/c").matches("a/b/d/c"));
+        assert!(filter("a/#").matches("a"));
+        assert!(filter("a/#").matches("a/b/c"));
+        assert!(!filter("a/#").matches("b"));
+        assert!(!filter("#").matches("$SYS/a"));
+        assert!(!filter("+/a").matches("$SYS/a"));
+        assert!(filter("$SYS/#").matches("$SYS/a"));
+    }
+
+    #[test]
+    fn routing() {
+        let mut tree = TopicTree::new();
+        let filters = ["a/b/c", "a/+/c", "a/#", "#", "+/b/+", "a/b", "$SYS/+", "a//c"];
+        for (i, filter) in filters.into_iter().enumerate() {
+            tree.subscribe(filter, i as i32).unwrap();
+        }
+        assert_eq!(route(&mut tree, "a/b/c"), [0, 1, 2, 3, 4]);
+        assert_eq!(route(&mut tree, "a/b"), [2, 3, 5]);
+        assert_eq!(route(&mut tree, "a"), [2, 3]);
+        assert_eq!(route(&mut tree, "a//c"), [1, 2, 3, 7]);
+        assert_eq!(route(&mut tree, "$SYS/uptime"), [6]);
+        assert_eq!(tree.route("a/+").err(), Some(TopicError::WildcardInTopic));
+        assert_eq!(tree.route("").err(), Some(TopicError::Empty));
+        // Results are always for the filters matching the topic
+        for topic in ["a/b/c", "a/b", "a", "b/b/b", "$SYS/a", "a//c", "x/y/z"] {
+            let expected = (0..filters.len())
+                .filter(|i| TopicFilter::new(filters[*i]).unwrap().matches(topic))
+                .map(|i| i as i32)
+                .collect::<Vec<_>>();
+            assert_eq!(route(&mut tree, topic), expected, "{}", topic);
+        }
+    }
+
+    #[test]
+    fn unsubscribing() {
+        let mut tree = TopicTree::new();
+        let a = tree.subscribe("a/+/c", 0).unwrap();
+        let b = tree.subscribe("a/b/#", 1).unwrap();
+        let c = tree.subscribe("a/b/c", 2).unwrap();
+        assert_eq!(tree.len(), 3);
+        assert_eq!(tree.filter(b).map(TopicFilter::as_str), Some("a/b/#"));
+        assert_eq!(tree.unsubscribe(b), Some(1));
+        assert_eq!(tree.unsubscribe(b), None);
+        assert_eq!(route(&mut tree, "a/b/c"), [0, 2]);
+        assert_eq!(tree.unsubscribe(a), Some(0));
+        assert_eq!(tree.unsubscribe(c), Some(2));
+        assert!(tree.is_empty());
+        assert!(tree.root.is_empty());
+    }
+}